@@ -0,0 +1,216 @@
+use std::any::Any;
+use std::collections::HashMap;
+use std::fs;
+use std::panic;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+
+use super::config::{self, Config};
+use super::formats;
+use super::hash::hash_bytes;
+
+// Remembers which image content and config produced each scheme in the output directory
+const MANIFEST: &str = ".reewal-batch";
+
+enum Outcome {
+    Generated,
+    Cached,
+    Failed(String),
+}
+
+struct Job {
+    image: PathBuf,
+    name: String,
+    save: PathBuf,
+}
+
+fn read_manifest(path: &Path) -> HashMap<String, (u64, u64)> {
+    let mut manifest = HashMap::new();
+    if let Ok(content) = fs::read_to_string(path) {
+        for line in content.lines() {
+            let fields: Vec<&str> = line.split('\t').collect();
+            if fields.len() != 3 {
+                continue;
+            }
+            if let (Ok(image_hash), Ok(config_hash)) = (
+                u64::from_str_radix(fields[1], 16),
+                u64::from_str_radix(fields[2], 16),
+            ) {
+                manifest.insert(fields[0].to_string(), (image_hash, config_hash));
+            }
+        }
+    }
+    manifest
+}
+
+fn write_manifest(path: &Path, manifest: &HashMap<String, (u64, u64)>) {
+    let mut names: Vec<&String> = manifest.keys().collect();
+    names.sort();
+    let mut output = String::new();
+    for name in names {
+        let hashes = manifest[name];
+        output += format!("{}\t{:016x}\t{:016x}\n", name, hashes.0, hashes.1).as_str();
+    }
+    fs::write(path, output).expect("Could not save batch manifest");
+}
+
+pub fn panic_message(payload: Box<dyn Any + Send>) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "Unknown error".to_string()
+    }
+}
+
+fn process(
+    job: &Job,
+    config: &Config,
+    config_hash: u64,
+    cached: Option<&(u64, u64)>,
+) -> (Outcome, Option<u64>) {
+    let bytes = match fs::read(&job.image) {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            return (
+                Outcome::Failed(format!("Could not read image: {}", e)),
+                None,
+            )
+        }
+    };
+    let image_hash = hash_bytes(&bytes);
+    if cached == Some(&(image_hash, config_hash)) && job.save.exists() {
+        return (Outcome::Cached, Some(image_hash));
+    }
+
+    let result = panic::catch_unwind(|| {
+        let (base16, _) = super::create_scheme(&bytes, config);
+        super::export_scheme(
+            &job.save.display().to_string(),
+            &base16,
+            Some(&bytes),
+            config,
+        );
+    });
+    match result {
+        Ok(()) => (Outcome::Generated, Some(image_hash)),
+        Err(payload) => (Outcome::Failed(panic_message(payload)), None),
+    }
+}
+
+pub fn run(mut args: Vec<String>) {
+    let out =
+        config::take_flag(&mut args, "--out").expect("--out: An output directory is required");
+    if args.len() < 2 {
+        panic!("Insufficient arguments provided. A wallpaper directory is required");
+    }
+    let dir = super::handle_path(args.pop().expect("No wallpaper directory provided"));
+    let out = PathBuf::from(super::handle_path(out));
+    let config = config::parse_config(args);
+    // Unlike the palette cache, post-processing changes the schemes on disk too
+    let config_hash = config.output_hash();
+
+    fs::create_dir_all(&out).expect("Could not create output directory");
+    let images: Vec<PathBuf> = fs::read_dir(&dir)
+        .expect("Could not read wallpaper directory")
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.is_file() && formats::is_supported(path))
+        .collect();
+    // a.png and a.jpg would both be saved as a.yaml, those keep their extension
+    let mut stems: HashMap<String, usize> = HashMap::new();
+    for path in &images {
+        let stem = path.file_stem().unwrap().to_string_lossy().to_string();
+        *stems.entry(stem).or_insert(0) += 1;
+    }
+    let mut jobs: Vec<Job> = images
+        .into_iter()
+        .map(|path| {
+            let name = path.file_name().unwrap().to_string_lossy().to_string();
+            let stem = path.file_stem().unwrap().to_string_lossy().to_string();
+            let base = if stems[&stem] > 1 { &name } else { &stem };
+            Job {
+                save: out.join(format!("{}.{}", base, config.format)),
+                image: path,
+                name,
+            }
+        })
+        .collect();
+    jobs.sort_by(|a, b| a.name.cmp(&b.name));
+    info!("Processing {} images from {}", jobs.len(), dir);
+
+    let manifest_path = out.join(MANIFEST);
+    let mut manifest = read_manifest(&manifest_path);
+
+    // Failures are reported in the summary, default hook would spam the output
+    let hook = panic::take_hook();
+    panic::set_hook(Box::new(|_| {}));
+
+    let next = AtomicUsize::new(0);
+    let workers = thread::available_parallelism()
+        .map(|x| x.get())
+        .unwrap_or(1)
+        .min(jobs.len().max(1));
+    let mut results: Vec<(usize, Outcome, Option<u64>)> = thread::scope(|scope| {
+        let handles: Vec<_> = (0..workers)
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut done = Vec::new();
+                    loop {
+                        let index = next.fetch_add(1, Ordering::Relaxed);
+                        if index >= jobs.len() {
+                            break;
+                        }
+                        let job = &jobs[index];
+                        let result = process(job, &config, config_hash, manifest.get(&job.name));
+                        done.push((index, result.0, result.1));
+                    }
+                    done
+                })
+            })
+            .collect();
+        handles
+            .into_iter()
+            .flat_map(|handle| handle.join().unwrap())
+            .collect()
+    });
+    panic::set_hook(hook);
+    results.sort_by_key(|x| x.0);
+
+    let width = jobs.iter().map(|x| x.name.len()).max().unwrap_or(0).max(5);
+    let mut counts = (0, 0, 0);
+    println!("{:<width$}  Result", "Image");
+    for (index, outcome, image_hash) in results {
+        let job = &jobs[index];
+        let status = match outcome {
+            Outcome::Generated => {
+                counts.0 += 1;
+                "generated".to_string()
+            }
+            Outcome::Cached => {
+                counts.1 += 1;
+                "cached".to_string()
+            }
+            Outcome::Failed(message) => {
+                counts.2 += 1;
+                format!("failed: {}", message)
+            }
+        };
+        match image_hash {
+            Some(hash) => manifest.insert(job.name.clone(), (hash, config_hash)),
+            None => manifest.remove(&job.name),
+        };
+        println!("{:<width$}  {}", job.name, status);
+    }
+    println!(
+        "{} generated, {} cached, {} failed",
+        counts.0, counts.1, counts.2
+    );
+    write_manifest(&manifest_path, &manifest);
+
+    if counts.2 > 0 {
+        std::process::exit(1);
+    }
+}
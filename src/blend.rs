@@ -0,0 +1,212 @@
+use std::io::{self, Write};
+use std::path::Path;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use super::color;
+use super::config;
+use super::data::Palette;
+use super::yaml;
+
+pub enum Space {
+    Oklab,
+    Oklch,
+}
+
+// Below this OKLCH chroma a color is gray enough that its hue means nothing
+const GRAY_CHROMA: f64 = 0.02;
+
+// base16-shell's mapping of the scheme onto the 16 ANSI colors and the extended 16-21
+const ANSI: [usize; 22] = [
+    0x00, 0x08, 0x0B, 0x0A, 0x0D, 0x0E, 0x0C, 0x05, 0x03, 0x08, 0x0B, 0x0A, 0x0D, 0x0E, 0x0C, 0x07,
+    0x09, 0x0F, 0x01, 0x02, 0x04, 0x06,
+];
+
+fn lerp(a: f64, b: f64, t: f64) -> f64 {
+    a + (b - a) * t
+}
+
+fn blend_oklab(a: &(u8, u8, u8), b: &(u8, u8, u8), t: f64) -> (u8, u8, u8) {
+    let (a, b) = (color::rgb_to_oklab(a), color::rgb_to_oklab(b));
+    color::oklab_to_rgb(&(lerp(a.0, b.0, t), lerp(a.1, b.1, t), lerp(a.2, b.2, t)))
+}
+
+fn blend_color(rgb1: &(u8, u8, u8), rgb2: &(u8, u8, u8), t: f64, space: &Space) -> (u8, u8, u8) {
+    match space {
+        Space::Oklab => blend_oklab(rgb1, rgb2, t),
+        Space::Oklch => {
+            let (a, b) = (color::rgb_to_oklch(rgb1), color::rgb_to_oklch(rgb2));
+            // Hue of a gray is noise, a straight line doesn't swing through other hues
+            if a.1 < GRAY_CHROMA || b.1 < GRAY_CHROMA {
+                return blend_oklab(rgb1, rgb2, t);
+            }
+            // Shortest way around the hue circle
+            let mut arc = b.2 - a.2;
+            if arc > 180.0 {
+                arc -= 360.0;
+            } else if arc < -180.0 {
+                arc += 360.0;
+            }
+            let hue = (a.2 + arc * t).rem_euclid(360.0);
+            color::oklch_to_rgb(&(lerp(a.0, b.0, t), lerp(a.1, b.1, t), hue))
+        }
+    }
+}
+
+// 0 gives a, 1 gives b, slots missing from either scheme stay missing
+pub fn blend(a: &Palette, b: &Palette, t: f64, space: &Space) -> Palette {
+    a.iter()
+        .zip(b)
+        .map(|(a, b)| Some(blend_color(a.as_ref()?, b.as_ref()?, t, space)))
+        .collect()
+}
+
+// OSC 4 for the ANSI colors, 10 and 11 for foreground and background, 12 for the cursor.
+// Colors of missing slots are left as they are
+fn osc_sequences(scheme: &Palette) -> String {
+    let rgb =
+        |slot: usize| scheme[slot].map(|c| format!("rgb:{:02x}/{:02x}/{:02x}", c.0, c.1, c.2));
+    let mut output = String::new();
+    for (index, slot) in ANSI.iter().enumerate() {
+        if let Some(color) = rgb(*slot) {
+            output += &format!("\x1b]4;{};{}\x1b\\", index, color);
+        }
+    }
+    for (code, slot) in [(10, 0x05), (11, 0x00), (12, 0x05)] {
+        if let Some(color) = rgb(slot) {
+            output += &format!("\x1b]{};{}\x1b\\", code, color);
+        }
+    }
+    output
+}
+
+fn parse_space(args: &mut Vec<String>) -> Space {
+    match config::take_flag(args, "--space").as_deref() {
+        None | Some("oklch") => Space::Oklch,
+        Some("oklab") => Space::Oklab,
+        Some(other) => panic!("--space: Expected oklab or oklch, got {}", other),
+    }
+}
+
+// Both schemes are the last two positional arguments
+fn load_schemes(args: &mut Vec<String>) -> [Palette; 2] {
+    if args.len() < 3 {
+        panic!("Insufficient arguments provided. Two scheme locations required");
+    }
+    let b = super::handle_path(args.pop().expect("No second scheme provided"));
+    let a = super::handle_path(args.pop().expect("No first scheme provided"));
+    config::set_verbosity(args);
+    [yaml::read_scheme(&a), yaml::read_scheme(&b)]
+}
+
+// reewal blend a.yaml b.yaml --t 0.3 [--out scheme.yaml]
+pub fn run_blend(mut args: Vec<String>) {
+    let space = parse_space(&mut args);
+    let t: f64 = config::take_flag(&mut args, "--t")
+        .map(|x| x.parse().expect("--t: Expected a number from 0 to 1"))
+        .unwrap_or(0.5);
+    if !(0.0..=1.0).contains(&t) {
+        panic!("--t: Incorrect value. Expected 0-1, got {}", t);
+    }
+    let out = super::handle_path(config::take_flag(&mut args, "--out").unwrap_or("-".to_string()));
+    let [a, b] = load_schemes(&mut args);
+    yaml::export_scheme(&out, &blend(&a, &b, t, &space));
+    info!("Saved blended scheme to {}", out);
+}
+
+// reewal transition a.yaml b.yaml --steps N --out scheme.yaml writes the N schemes in
+// between as scheme-001.yaml and so on. Without --out the terminal is faded from a to
+// b over --duration seconds with OSC escape sequences
+pub fn run_transition(mut args: Vec<String>) {
+    let space = parse_space(&mut args);
+    let out = config::take_flag(&mut args, "--out").map(super::handle_path);
+    let duration: f64 = config::take_flag(&mut args, "--duration")
+        .map(|x| x.parse().expect("--duration: Expected a time in seconds"))
+        .unwrap_or(1.0);
+    if duration <= 0.0 {
+        panic!("--duration: Expected a time > 0, got {}", duration);
+    }
+    let steps: usize = config::take_flag(&mut args, "--steps")
+        .map(|x| x.parse().expect("--steps: Expected a number of schemes"))
+        .unwrap_or(((duration * 30.0).round() as usize).max(1));
+    if steps == 0 {
+        panic!("--steps: Expected at least 1 step");
+    }
+    let [a, b] = load_schemes(&mut args);
+
+    match out {
+        Some(out) => {
+            let path = Path::new(&out);
+            let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+            let extension = path.extension().unwrap_or_default().to_string_lossy();
+            for step in 1..=steps {
+                let t = step as f64 / (steps + 1) as f64;
+                let save = path
+                    .with_file_name(format!("{}-{:03}.{}", stem, step, extension))
+                    .display()
+                    .to_string();
+                yaml::export_scheme(&save, &blend(&a, &b, t, &space));
+                verbose!("Saved step {} (t {:.3}) to {}", step, t, save);
+            }
+            info!("Saved {} intermediate schemes", steps);
+        }
+        None => {
+            let interval = Duration::from_secs_f64(duration / steps as f64);
+            let start = Instant::now();
+            let mut stdout = io::stdout().lock();
+            for step in 1..=steps {
+                let t = step as f64 / steps as f64;
+                let sequences = osc_sequences(&blend(&a, &b, t, &space));
+                if stdout
+                    .write_all(sequences.as_bytes())
+                    .and_then(|_| stdout.flush())
+                    .is_err()
+                {
+                    return;
+                }
+                // Sleeping until the step's time keeps slow terminals from stretching the fade
+                let next = interval * step as u32;
+                if let Some(wait) = next.checked_sub(start.elapsed()) {
+                    thread::sleep(wait);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn schemes() -> (Palette, Palette) {
+        (
+            vec![Some((204, 36, 29)), Some((40, 40, 40)), None],
+            vec![Some((69, 133, 136)), Some((220, 220, 220)), Some((1, 2, 3))],
+        )
+    }
+
+    #[test]
+    fn ends_are_the_schemes() {
+        let (a, b) = schemes();
+        for space in [Space::Oklab, Space::Oklch] {
+            assert_eq!(blend(&a, &b, 0.0, &space)[..2], a[..2]);
+            assert_eq!(blend(&a, &b, 1.0, &space)[..2], b[..2]);
+        }
+    }
+
+    #[test]
+    fn missing_slots_stay_missing() {
+        let (a, b) = schemes();
+        assert_eq!(blend(&a, &b, 0.5, &Space::Oklch)[2], None);
+    }
+
+    #[test]
+    fn hue_takes_the_short_way() {
+        // Red at about 29 degrees and magenta at about 328, halfway is near 0, not 180
+        let a = vec![Some((255, 0, 0))];
+        let b = vec![Some((255, 0, 255))];
+        let middle = blend(&a, &b, 0.5, &Space::Oklch)[0].unwrap();
+        let hue = color::rgb_to_oklch(&middle).2;
+        assert!(!(90.0..270.0).contains(&hue), "hue {}", hue);
+    }
+}
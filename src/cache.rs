@@ -0,0 +1,132 @@
+use std::collections::BinaryHeap;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use super::quantize::ColorCount;
+
+// Results are stored as plain text under $XDG_CACHE_HOME/reewal, failing to read or
+// write the cache is never fatal, the value just gets recomputed
+fn cache_dir() -> Option<PathBuf> {
+    let base = match env::var("XDG_CACHE_HOME") {
+        Ok(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => PathBuf::from(env::var("HOME").ok()?).join(".cache"),
+    };
+    let dir = base.join("reewal");
+    fs::create_dir_all(&dir).ok()?;
+    Some(dir)
+}
+
+fn entry_path(image_hash: u64, settings_hash: u64, kind: &str) -> Option<PathBuf> {
+    Some(cache_dir()?.join(format!(
+        "{:016x}-{:016x}.{}",
+        image_hash, settings_hash, kind
+    )))
+}
+
+// First line of every entry, bumped whenever the format changes so older entries
+// are recomputed instead of misread
+const HEADER: &str = "reewal-cache 1";
+
+// Entries are written next to their final name and renamed into place, so readers
+// in other threads or processes never see half of one
+fn write_entry(path: &Path, content: &str) {
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+    let temporary = path.with_extension(format!(
+        "tmp-{}-{}",
+        std::process::id(),
+        COUNTER.fetch_add(1, Ordering::Relaxed)
+    ));
+    let written = fs::write(&temporary, format!("{}\n{}", HEADER, content));
+    if written.and_then(|_| fs::rename(&temporary, path)).is_err() {
+        let _ = fs::remove_file(&temporary);
+    }
+}
+
+// Content after the header, None for entries of another format version
+fn read_entry(path: &Path) -> Option<String> {
+    let content = fs::read_to_string(path).ok()?;
+    let (header, content) = content.split_once('\n')?;
+    (header == HEADER).then(|| content.to_string())
+}
+
+fn parse_hex(hex: &str) -> Option<(u8, u8, u8)> {
+    if hex.len() != 6 {
+        return None;
+    }
+    Some((
+        u8::from_str_radix(&hex[0..2], 16).ok()?,
+        u8::from_str_radix(&hex[2..4], 16).ok()?,
+        u8::from_str_radix(&hex[4..6], 16).ok()?,
+    ))
+}
+
+// Quantized histogram only depends on the image and the quantizer settings, kind
+// tells apart differently weighted histograms of the same image
+pub fn load_histogram(
+    image_hash: u64,
+    quantize_hash: u64,
+    kind: &str,
+) -> Option<BinaryHeap<ColorCount>> {
+    let content = read_entry(&entry_path(image_hash, quantize_hash, kind)?)?;
+    let mut colors = BinaryHeap::new();
+    for line in content.lines() {
+        let (hex, count) = line.split_once(' ')?;
+        colors.push(ColorCount {
+            rgb: parse_hex(hex)?,
+            count: count.parse().ok()?,
+        });
+    }
+    Some(colors)
+}
+
+pub fn save_histogram(
+    image_hash: u64,
+    quantize_hash: u64,
+    kind: &str,
+    colors: &BinaryHeap<ColorCount>,
+) {
+    if let Some(path) = entry_path(image_hash, quantize_hash, kind) {
+        let mut output = String::new();
+        // Most frequent first, so the same histogram always makes the same file
+        for color in colors.clone().into_sorted_vec().iter().rev() {
+            output += format!(
+                "{:02X}{:02X}{:02X} {}\n",
+                color.rgb.0, color.rgb.1, color.rgb.2, color.count
+            )
+            .as_str();
+        }
+        write_entry(&path, &output);
+    }
+}
+
+// Final palette depends on every setting, missing slots are stored as "-"
+pub fn load_palette(image_hash: u64, config_hash: u64) -> Option<Vec<Option<(u8, u8, u8)>>> {
+    let content = read_entry(&entry_path(image_hash, config_hash, "palette")?)?;
+    let mut palette = Vec::new();
+    for line in content.lines() {
+        if line == "-" {
+            palette.push(None);
+        } else {
+            palette.push(Some(parse_hex(line)?));
+        }
+    }
+    if palette.len() != 16 {
+        return None;
+    }
+    Some(palette)
+}
+
+pub fn save_palette(image_hash: u64, config_hash: u64, palette: &[Option<(u8, u8, u8)>]) {
+    if let Some(path) = entry_path(image_hash, config_hash, "palette") {
+        let mut output = String::new();
+        for color in palette.iter() {
+            match color {
+                Some(c) => output += format!("{:02X}{:02X}{:02X}\n", c.0, c.1, c.2).as_str(),
+                None => output += "-\n",
+            }
+        }
+        write_entry(&path, &output);
+    }
+}
@@ -0,0 +1,107 @@
+use image::{GenericImageView, Rgb, RgbImage};
+use std::collections::{BTreeMap, BinaryHeap};
+
+use super::config;
+use super::data::{self, hex, SlotReport};
+use super::frames;
+use super::quantize::{self, ColorCount};
+use super::saliency;
+
+// Slot a cluster ended up in, None when it was only a candidate or fed no slot at all
+fn assigned_slot(
+    rgb: &(u8, u8, u8),
+    accent: &(u8, u8, u8),
+    reports: &[SlotReport],
+) -> Option<usize> {
+    if rgb == accent {
+        return Some(13);
+    }
+    reports
+        .iter()
+        .position(|x| !x.generated && x.chosen.as_ref().is_some_and(|x| x.rgb == *rgb))
+}
+
+// Paints every pixel of the (first frame of the) image with the palette color of the
+// slot its cluster was assigned to, black when it fed none, with a strip of the 16
+// slots in order below it
+pub fn run(mut args: Vec<String>) {
+    if args.len() < 3 {
+        panic!("Insufficient arguments provided. An image and output image locations required");
+    }
+    let save = super::handle_path(args.pop().expect("No output image provided"));
+    let image = args.pop().expect("No image provided");
+    let config = config::parse_config(args);
+    let bytes = super::read_image(image);
+
+    let frames = frames::decode(&bytes, &config);
+    let clusters = quantize::clusters(&frames, config.depth, config.background, |x| {
+        saliency::pixel_weight(x, &config)
+    });
+    let mut colors: BinaryHeap<ColorCount> = clusters.values().cloned().collect();
+    let centered =
+        super::needs_center(&config).then(|| super::histogram(&frames, &config, "center"));
+    let dominant = data::dominant(&colors, &config);
+    let (accent, _) = data::get_accent(&mut colors, centered.as_ref(), &config);
+    let mut candidates = data::map_colors(colors, &config);
+    let (palette, reports) = data::explain_palette(&mut candidates, accent, dominant, &config);
+
+    let slots: BTreeMap<&String, Option<usize>> = clusters
+        .iter()
+        .map(|(bucket, cluster)| (bucket, assigned_slot(&cluster.rgb, &accent, &reports)))
+        .collect();
+
+    let img = &frames[0].image;
+    let (width, height) = img.dimensions();
+    let legend = (height / 12).max(16);
+    let weight = saliency::pixel_weight(img, &config);
+    let pixels = img.to_rgba16();
+    let mut output = RgbImage::new(width, height + legend);
+    let mut counts = [0usize; 16];
+    let mut unassigned = 0usize;
+    for (x, y, pixel) in pixels.enumerate_pixels() {
+        let slot = quantize::pixel_color(&pixel.0, config.background)
+            .filter(|_| weight(x, y) > 0)
+            .and_then(|(rgb, _)| {
+                slots
+                    .get(&quantize::bucket_hash(&rgb, config.depth))
+                    .copied()
+            })
+            .flatten();
+        let rgb = match slot.and_then(|x| palette[x].map(|rgb| (x, rgb))) {
+            Some((index, rgb)) => {
+                counts[index] += 1;
+                rgb
+            }
+            None => {
+                unassigned += 1;
+                (0, 0, 0)
+            }
+        };
+        output.put_pixel(x, y, Rgb([rgb.0, rgb.1, rgb.2]));
+    }
+    for x in 0..width {
+        let index = (x as u64 * 16 / width as u64) as usize;
+        let rgb = palette[index].unwrap_or((0, 0, 0));
+        for y in height..height + legend {
+            output.put_pixel(x, y, Rgb([rgb.0, rgb.1, rgb.2]));
+        }
+    }
+    output.save(&save).expect("Could not save cluster map");
+
+    let total = (width as f64 * height as f64).max(1.0);
+    for (index, count) in counts.iter().enumerate() {
+        let color = palette[index].map_or("-".to_string(), |x| hex(&x));
+        println!(
+            "base{:02X}  {:<7}  {:>5.1}%",
+            index,
+            color,
+            *count as f64 * 100.0 / total
+        );
+    }
+    println!(
+        "none    {:<7}  {:>5.1}%",
+        "#000000",
+        unassigned as f64 * 100.0 / total
+    );
+    info!("Saved cluster map to {}", save);
+}
@@ -1,6 +1,6 @@
 use std::f64::consts::PI;
 
-fn rgb_to_hsl(rgb: &(u8, u8, u8)) -> (f64, f64, f64) {
+pub fn rgb_to_hsl(rgb: &(u8, u8, u8)) -> (f64, f64, f64) {
     let r: f64 = rgb.0 as f64 / 255.0;
     let g: f64 = rgb.1 as f64 / 255.0;
     let b: f64 = rgb.2 as f64 / 255.0;
@@ -97,7 +97,7 @@ pub fn is_colorful(rgb: &(u8, u8, u8), threshold: u8) -> bool {
 }
 
 // Needed for color comparison
-fn rgb_to_lab(rgb: &(u8, u8, u8)) -> (f64, f64, f64) {
+pub fn rgb_to_lab(rgb: &(u8, u8, u8)) -> (f64, f64, f64) {
     // Convert to XYZ, as rgb can't really be converted straight to lab
     let mut r: f64 = rgb.0 as f64 / 255.0;
     let mut g: f64 = rgb.1 as f64 / 255.0;
@@ -146,6 +146,80 @@ fn rgb_to_lab(rgb: &(u8, u8, u8)) -> (f64, f64, f64) {
     (l, a, b)
 }
 
+pub fn srgb_to_linear(c: u8) -> f64 {
+    let c = c as f64 / 255.0;
+    if c > 0.04045 {
+        ((c + 0.055) / 1.055).powf(2.4)
+    } else {
+        c / 12.92
+    }
+}
+
+pub fn linear_to_srgb(c: f64) -> u8 {
+    let c = if c > 0.0031308 {
+        1.055 * c.powf(1.0 / 2.4) - 0.055
+    } else {
+        12.92 * c
+    };
+    (c * 255.0).round().clamp(0.0, 255.0) as u8
+}
+
+// OKLab by Björn Ottosson, perceptually uniform enough to interpolate in
+pub fn rgb_to_oklab(rgb: &(u8, u8, u8)) -> (f64, f64, f64) {
+    let r = srgb_to_linear(rgb.0);
+    let g = srgb_to_linear(rgb.1);
+    let b = srgb_to_linear(rgb.2);
+
+    let l = (0.4122214708 * r + 0.5363325363 * g + 0.0514459929 * b).cbrt();
+    let m = (0.2119034982 * r + 0.6806995451 * g + 0.1073969566 * b).cbrt();
+    let s = (0.0883024619 * r + 0.2817188376 * g + 0.6299787005 * b).cbrt();
+
+    (
+        0.2104542553 * l + 0.7936177850 * m - 0.0040720468 * s,
+        1.9779984951 * l - 2.4285922050 * m + 0.4505937099 * s,
+        0.0259040371 * l + 0.7827717662 * m - 0.8086757660 * s,
+    )
+}
+
+pub fn oklab_to_rgb(lab: &(f64, f64, f64)) -> (u8, u8, u8) {
+    let l = (lab.0 + 0.3963377774 * lab.1 + 0.2158037573 * lab.2).powi(3);
+    let m = (lab.0 - 0.1055613458 * lab.1 - 0.0638541728 * lab.2).powi(3);
+    let s = (lab.0 - 0.0894841775 * lab.1 - 1.2914855480 * lab.2).powi(3);
+
+    (
+        linear_to_srgb(4.0767416621 * l - 3.3077115913 * m + 0.2309699292 * s),
+        linear_to_srgb(-1.2684380046 * l + 2.6097574011 * m - 0.3413193965 * s),
+        linear_to_srgb(-0.0041960863 * l - 0.7034186147 * m + 1.7076147010 * s),
+    )
+}
+
+// Lightness, chroma and hue in degrees of OKLab
+pub fn rgb_to_oklch(rgb: &(u8, u8, u8)) -> (f64, f64, f64) {
+    let lab = rgb_to_oklab(rgb);
+    let hue = lab.2.atan2(lab.1).to_degrees();
+    (
+        lab.0,
+        (lab.1.powi(2) + lab.2.powi(2)).sqrt(),
+        (hue + 360.0) % 360.0,
+    )
+}
+
+pub fn oklch_to_rgb(lch: &(f64, f64, f64)) -> (u8, u8, u8) {
+    let hue = lch.2.to_radians();
+    oklab_to_rgb(&(lch.0, lch.1 * hue.cos(), lch.1 * hue.sin()))
+}
+
+// Hue angle in degrees, meaningless for grayscale colors
+pub fn get_hue(rgb: &(u8, u8, u8)) -> f64 {
+    rgb_to_hsl(rgb).0 * 360.0
+}
+
+// Chroma from L*ab, how far the color is from the gray axis
+pub fn get_chroma(rgb: &(u8, u8, u8)) -> f64 {
+    let lab = rgb_to_lab(rgb);
+    (lab.1.powi(2) + lab.2.powi(2)).sqrt()
+}
+
 // Calculate the delta E of colors using CIE2000
 // with ability to fine tune weight factors
 pub fn compare_colors(
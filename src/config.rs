@@ -1,11 +1,50 @@
-use super::yaml::get_scheme;
+use saphyr::Yaml;
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::time::Duration;
+
+use super::cvd::Deficiency;
+use super::data::AccentStrategy;
+use super::hash::Fnv;
+use super::hooks::Hook;
+use super::log::{self, Level};
+use super::pipeline::{slot_group, Operation, Stage};
+use super::saliency::Mask;
+use super::yaml::{get_scheme, hex_to_rgb};
 
 pub struct Config {
     pub template_colors: Vec<(u8, u8, u8)>,
     pub depth: u8,
+    pub center_bias: u8,
+    pub saliency: u8,
+    pub masks: Vec<Mask>,
+    pub background: Option<(u8, u8, u8)>,
+    pub frames: usize,
+    pub per_frame: bool,
+    // Output only, neither is part of the hashes
+    pub format: String,
+    pub histogram: bool,
+    pub auto_tune: bool,
+    // Post-processing, none of it is part of the hashes either
+    pub pipeline: Vec<Stage>,
+    pub temperature: Option<f64>,
+    // Extra schemes exported with the name as suffix, adapted to their temperature
+    pub variants: Vec<(String, f64)>,
+    pub svg_size: u32,
+    pub exposure: f64,
     pub similarity: u16,
     pub vibrancy: u8,
     pub likeness: u16,
+    // De-duplication also compares the colors as seen with these
+    pub cvd: Vec<Deficiency>,
+    pub cvd_check: bool,
+    pub cvd_delta: u16,
+    // Even grayscale ramp with steps of at least ramp_step OKLCH lightness percent
+    pub gray_ramp: bool,
+    pub ramp_step: u8,
+    // Slots given a fixed color, kept no matter what the image has
+    pub locks: Vec<(usize, (u8, u8, u8))>,
+    pub accent_strategy: AccentStrategy,
 
     pub hue_compare: f64,
     pub chroma_compare: f64,
@@ -18,6 +57,518 @@ pub struct Config {
     pub hue_tweak: i8,
     pub saturation_tweak: i8,
     pub light_tweak: i8,
+
+    pub hooks: Vec<Hook>,
+    pub parallel_hooks: bool,
+}
+
+impl Config {
+    // Stable identifier of the settings that affect quantize::quantize output
+    pub fn quantize_hash(&self) -> u64 {
+        let mut hasher = Fnv::default();
+        self.depth.hash(&mut hasher);
+        self.center_bias.hash(&mut hasher);
+        self.saliency.hash(&mut hasher);
+        self.masks.hash(&mut hasher);
+        self.background.hash(&mut hasher);
+        self.frames.hash(&mut hasher);
+        self.svg_size.hash(&mut hasher);
+        self.exposure.to_bits().hash(&mut hasher);
+        hasher.finish()
+    }
+
+    // Stable identifier of every setting that affects the generated palette
+    pub fn hash(&self) -> u64 {
+        let mut hasher = Fnv::default();
+        self.quantize_hash().hash(&mut hasher);
+        self.template_colors.hash(&mut hasher);
+        self.similarity.hash(&mut hasher);
+        self.vibrancy.hash(&mut hasher);
+        self.likeness.hash(&mut hasher);
+        self.cvd.hash(&mut hasher);
+        self.gray_ramp.hash(&mut hasher);
+        self.ramp_step.hash(&mut hasher);
+        self.locks.hash(&mut hasher);
+        match self.accent_strategy {
+            AccentStrategy::Frequent => 0.hash(&mut hasher),
+            AccentStrategy::Saturated => 1.hash(&mut hasher),
+            AccentStrategy::Score => 2.hash(&mut hasher),
+            AccentStrategy::Center => 3.hash(&mut hasher),
+            AccentStrategy::Hint(rgb) => (4, rgb).hash(&mut hasher),
+            AccentStrategy::Complementary => 5.hash(&mut hasher),
+        }
+        self.hue_compare.to_bits().hash(&mut hasher);
+        self.chroma_compare.to_bits().hash(&mut hasher);
+        self.light_compare.to_bits().hash(&mut hasher);
+        self.hue_mix.hash(&mut hasher);
+        self.saturation_mix.hash(&mut hasher);
+        self.light_mix.hash(&mut hasher);
+        self.hue_tweak.hash(&mut hasher);
+        self.saturation_tweak.hash(&mut hasher);
+        self.light_tweak.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    // hash along with the output and post-processing settings it leaves out, for
+    // telling whether a scheme exported by batch is still up to date. Variants aren't
+    // part of it, batch doesn't write them
+    pub fn output_hash(&self) -> u64 {
+        let mut hasher = Fnv::default();
+        self.hash().hash(&mut hasher);
+        self.format.hash(&mut hasher);
+        self.histogram.hash(&mut hasher);
+        self.pipeline.hash(&mut hasher);
+        self.temperature.map(f64::to_bits).hash(&mut hasher);
+        hasher.finish()
+    }
+
+    pub fn locked(&self, index: usize) -> Option<(u8, u8, u8)> {
+        self.locks.iter().find(|x| x.0 == index).map(|x| x.1)
+    }
+}
+
+// Remove a flag and its value from the arguments, so subcommands can pick out
+// their own options before the rest is handed to parse_config
+pub fn take_flag(args: &mut Vec<String>, flag: &str) -> Option<String> {
+    let index = args.iter().position(|x| x == flag)?;
+    if index + 1 >= args.len() {
+        panic!("{}: Expected a value", flag);
+    }
+    let value = args.remove(index + 1);
+    args.remove(index);
+    Some(value)
+}
+
+const SWITCHES: [&str; 5] = [
+    "--per-frame",
+    "--histogram",
+    "--cvd-check",
+    "--auto-tune",
+    "--gray-ramp",
+];
+
+// -v is also the vibrancy flag, it's only a verbosity switch when no number follows
+pub fn is_verbosity(args: &[String], index: usize) -> bool {
+    match args[index].as_str() {
+        "-q" | "-vv" => true,
+        "-v" => args
+            .get(index + 1)
+            .is_none_or(|x| x.parse::<i64>().is_err()),
+        _ => false,
+    }
+}
+
+// Picked up before anything else so loading the template and config file already
+// logs at the right level
+pub fn set_verbosity(args: &[String]) {
+    for index in 1..args.len() {
+        if is_verbosity(args, index) {
+            log::set_level(match args[index].as_str() {
+                "-q" => Level::Quiet,
+                "-v" => Level::Verbose,
+                _ => Level::Debug,
+            });
+        }
+    }
+}
+
+fn parse_kelvin(flag: &str, value: &str) -> f64 {
+    let kelvin: f64 = value
+        .trim()
+        .trim_end_matches(['K', 'k'])
+        .parse()
+        .unwrap_or_else(|_| panic!("{}: Expected a color temperature in kelvin", flag));
+    if !(1667.0..=25000.0).contains(&kelvin) {
+        panic!("{}: Expected 1667-25000K, got {}", flag, kelvin);
+    }
+    kelvin
+}
+
+// Apply a single option, returns false when the flag isn't known
+fn set_option(config: &mut Config, flag: &str, value: &str) -> bool {
+    if flag == "-t" {
+        let path = value.to_string();
+        if path.starts_with("/") || path.starts_with("\\") {
+            config.template_colors = get_scheme(&path)
+        } else {
+            let current_dir = std::env::current_dir().unwrap().display().to_string();
+            config.template_colors = get_scheme(&format!("{}/{}", current_dir, path));
+        }
+    } else if flag == "-d" {
+        let depth: i64 = value.parse().unwrap();
+        if !(1..=8).contains(&depth) {
+            panic!("-d: Incorrect depth value. Expected 1-8, got {}", depth);
+        } else {
+            config.depth = depth.try_into().expect("Unknown error when parsing depth");
+        }
+    } else if flag == "--center-bias" || flag == "--saliency" {
+        let input: u8 = value
+            .parse()
+            .unwrap_or_else(|_| panic!("{}: Expected 0-100, got {}", flag, value));
+        if input > 100 {
+            panic!("{}: Incorrect value. Expected 0-100, got {}", flag, input);
+        } else if flag == "--center-bias" {
+            config.center_bias = input;
+        } else {
+            config.saliency = input;
+        }
+    } else if flag == "--mask" {
+        config.masks.push(Mask::parse(value).unwrap_or_else(|| {
+            panic!(
+                "--mask: Expected x,y,width,height in pixels or percent, got {}",
+                value
+            )
+        }));
+    } else if flag == "--background" {
+        config.background = Some(hex_to_rgb(value));
+    } else if flag == "--frames" {
+        config.frames = match value {
+            "all" => 0,
+            _ => value
+                .parse()
+                .expect("--frames: Expected all or a number of frames"),
+        };
+    } else if flag == "--format" {
+        if !matches!(value, "yaml" | "json") {
+            panic!("--format: Expected yaml or json, got {}", value);
+        }
+        config.format = value.to_string();
+    } else if flag == "--auto-tune" {
+        config.auto_tune = value.parse().expect("--auto-tune: Expected true or false");
+    } else if flag == "--temperature" {
+        config.temperature = Some(parse_kelvin(flag, value));
+    } else if flag == "--variants" {
+        // day=6500,evening=4000,night=2700
+        config.variants = value
+            .split(',')
+            .map(|x| {
+                let (name, kelvin) = x
+                    .split_once('=')
+                    .unwrap_or_else(|| panic!("{}: Expected name=kelvin, got {}", flag, x));
+                (name.trim().to_string(), parse_kelvin(flag, kelvin))
+            })
+            .collect();
+    } else if flag == "--histogram" {
+        config.histogram = value.parse().expect("--histogram: Expected true or false");
+    } else if flag == "--per-frame" {
+        config.per_frame = value.parse().expect("--per-frame: Expected true or false");
+    } else if flag == "--svg-size" {
+        config.svg_size = value
+            .parse()
+            .expect("--svg-size: Expected a size in pixels");
+        if config.svg_size == 0 {
+            panic!("--svg-size: Incorrect value. Expected a size > 0");
+        }
+    } else if flag == "--exposure" {
+        config.exposure = value
+            .parse()
+            .expect("--exposure: Expected a float, in stops");
+    } else if flag == "-s" {
+        config.similarity = value
+            .parse()
+            .expect("-s: Incorrect variance value. Expected an unsigned 16bit integer");
+    } else if flag == "-v" {
+        let input: i64 = value.parse().unwrap();
+        if !(1..=100).contains(&input) {
+            panic!(
+                "-c: Incorrect color threshold value. Expected 1-100, got {}",
+                input
+            );
+        } else {
+            config.vibrancy = input
+                .try_into()
+                .expect("Unknown error when parsing color threshold");
+        }
+    } else if flag == "-l" {
+        config.likeness = value
+            .parse()
+            .expect("-l: Expected an unsigned 16bit integer");
+    } else if flag == "--cvd" {
+        config.cvd = value
+            .split(',')
+            .map(|x| {
+                Deficiency::parse(x.trim()).unwrap_or_else(|| {
+                    panic!("--cvd: Expected protan, deutan or tritan, got {}", x)
+                })
+            })
+            .collect();
+    } else if flag == "--cvd-check" {
+        config.cvd_check = value.parse().expect("--cvd-check: Expected true or false");
+    } else if flag == "--cvd-delta" {
+        config.cvd_delta = value
+            .parse()
+            .expect("--cvd-delta: Expected an unsigned 16bit integer");
+    } else if flag == "--gray-ramp" {
+        config.gray_ramp = value.parse().expect("--gray-ramp: Expected true or false");
+    } else if flag == "--ramp-step" {
+        let input: u8 = value
+            .parse()
+            .unwrap_or_else(|_| panic!("{}: Expected 1-14, got {}", flag, value));
+        if !(1..=14).contains(&input) {
+            panic!("{}: Incorrect value. Expected 1-14, got {}", flag, input);
+        }
+        config.ramp_step = input;
+    } else if flag == "--lock" {
+        // base08=#cc241d, locking the same slot again replaces the color
+        let (slot, hex) = value
+            .split_once('=')
+            .unwrap_or_else(|| panic!("--lock: Expected slot=#hex, got {}", value));
+        let index = match slot_group(slot.trim()).as_deref() {
+            Some([index]) => *index,
+            _ => panic!("--lock: Expected a slot like base08, got {}", slot),
+        };
+        config.locks.retain(|x| x.0 != index);
+        config.locks.push((index, hex_to_rgb(hex.trim())));
+    } else if flag == "--accent" {
+        config.accent_strategy = match value {
+            "frequent" => AccentStrategy::Frequent,
+            "saturated" => AccentStrategy::Saturated,
+            "score" => AccentStrategy::Score,
+            "center" => AccentStrategy::Center,
+            "complementary" => AccentStrategy::Complementary,
+            hint if hint.starts_with('#') => AccentStrategy::Hint(hex_to_rgb(hint)),
+            _ => panic!(
+                "--accent: Expected frequent, saturated, score, center, complementary or a #hex hint, got {}",
+                value
+            ),
+        };
+    } else if flag == "--hue-compare" {
+        let input: f64 = value.parse().expect("--hue-compare: Expected a float");
+        if input <= 0.0 {
+            panic!(
+                "--hue-compare: Incorrect value. Expected a float > 0.0, got {}",
+                input
+            );
+        } else {
+            config.hue_compare = input;
+        }
+    } else if flag == "--chroma-compare" {
+        let input: f64 = value.parse().expect("--chroma-compare: Expected a float");
+        if input <= 0.0 {
+            panic!(
+                "--chroma-compare: Incorrect value. Expected a float > 0.0, got {}",
+                input
+            );
+        } else {
+            config.chroma_compare = input;
+        }
+    } else if flag == "--light-compare" {
+        let input: f64 = value.parse().expect("--light-compare: Expected a float");
+        if input <= 0.0 {
+            panic!(
+                "--light-compare: Incorrect value. Expected a float > 0.0, got {}",
+                input
+            );
+        } else {
+            config.light_compare = input;
+        }
+    } else if flag == "--hue-mix" {
+        config.hue_mix = value.parse().expect("--hue-mix: Expected an 8bit integer");
+    } else if flag == "--saturation-mix" {
+        config.saturation_mix = value
+            .parse()
+            .expect("--saturation-mix: Expected an 8bit integer");
+    } else if flag == "--light-mix" {
+        config.light_mix = value
+            .parse()
+            .expect("--light-mix: Expected an 8bit integer");
+    } else if flag == "--hue-tweak" {
+        config.hue_tweak = value
+            .parse()
+            .expect("--hue-tweak: Expected an 8bit integer");
+    } else if flag == "--saturation-tweak" {
+        config.saturation_tweak = value
+            .parse()
+            .expect("--saturation-tweak: Expected an 8bit integer");
+    } else if flag == "--light-tweak" {
+        config.light_tweak = value
+            .parse()
+            .expect("--light-tweak: Expected an 8bit integer");
+    } else {
+        return false;
+    }
+    true
+}
+
+// Seconds, either written as an integer or a float
+fn parse_seconds(value: &Yaml, name: &str) -> Duration {
+    let seconds = value
+        .as_f64()
+        .or(value.as_i64().map(|x| x as f64))
+        .unwrap_or_else(|| panic!("{}: Expected a time in seconds", name));
+    if seconds <= 0.0 {
+        panic!("{}: Expected a time > 0, got {}", name, seconds);
+    }
+    Duration::from_secs_f64(seconds)
+}
+
+// hooks:
+//   parallel: true
+//   timeout: 10
+//   run:
+//     - makoctl reload
+//     - command: pkill -SIGUSR2 waybar
+//       timeout: 2
+fn parse_hooks(config: &mut Config, hooks: &Yaml) {
+    let mut timeout = Duration::from_secs(10);
+    if !hooks["timeout"].is_badvalue() {
+        timeout = parse_seconds(&hooks["timeout"], "hooks.timeout");
+    }
+    if !hooks["parallel"].is_badvalue() {
+        config.parallel_hooks = hooks["parallel"]
+            .as_bool()
+            .expect("hooks.parallel: Expected true or false");
+    }
+    let commands = hooks["run"]
+        .as_vec()
+        .expect("hooks.run: Expected a list of commands");
+    for command in commands {
+        let hook = if let Some(command) = command.as_str() {
+            Hook {
+                command: command.to_string(),
+                timeout,
+            }
+        } else {
+            Hook {
+                command: command["command"]
+                    .as_str()
+                    .expect("hooks.run: Expected a command string")
+                    .to_string(),
+                timeout: if command["timeout"].is_badvalue() {
+                    timeout
+                } else {
+                    parse_seconds(&command["timeout"], "hooks.run.timeout")
+                },
+            }
+        };
+        config.hooks.push(hook);
+    }
+}
+
+fn parse_number(value: &Yaml, name: &str) -> f64 {
+    value
+        .as_f64()
+        .or(value.as_i64().map(|x| x as f64))
+        .unwrap_or_else(|| panic!("{}: Expected a number", name))
+}
+
+// pipeline:
+//   - stage: desaturate
+//     amount: 20
+//     slots: grayscale
+//   - stage: harmonize
+//     amount: 30
+//   - stage: contrast
+//     min: 20
+//     max: 95
+//   - stage: clamp-chroma
+//     max: 0.12
+// Amounts and contrast min/max are percentages, hue-rotate takes degrees and
+// clamp-chroma's max is an OKLCH chroma (0-0.4), chroma has no natural full scale.
+// Slots are all, grayscale, accents or a list of slot names, by default contrast
+// works on the grayscale slots, harmonize on the accents and the rest on all of them
+fn parse_pipeline(config: &mut Config, pipeline: &Yaml) {
+    let stages = pipeline
+        .as_vec()
+        .expect("pipeline: Expected a list of stages");
+    for stage in stages {
+        let name = stage["stage"]
+            .as_str()
+            .expect("pipeline.stage: Expected a stage name");
+        let amount = || parse_number(&stage["amount"], &format!("pipeline.{}.amount", name));
+        let operation = match name {
+            "saturate" => Operation::Saturate(amount()),
+            "desaturate" => Operation::Saturate(-amount()),
+            "lighten" => Operation::Lighten(amount()),
+            "darken" => Operation::Lighten(-amount()),
+            "hue-rotate" => Operation::HueRotate(parse_number(
+                &stage["degrees"],
+                "pipeline.hue-rotate.degrees",
+            )),
+            "contrast" => Operation::ContrastStretch {
+                min: parse_number(&stage["min"], "pipeline.contrast.min") / 100.0,
+                max: parse_number(&stage["max"], "pipeline.contrast.max") / 100.0,
+            },
+            "harmonize" => Operation::Harmonize(amount()),
+            "clamp-chroma" => {
+                let max = parse_number(&stage["max"], "pipeline.clamp-chroma.max");
+                if !(0.0..=0.5).contains(&max) {
+                    panic!(
+                        "pipeline.clamp-chroma.max: Expected an OKLCH chroma like 0.12, got {}",
+                        max
+                    );
+                }
+                Operation::ClampChroma(max)
+            }
+            _ => panic!("pipeline: Unknown stage {}", name),
+        };
+        let default = match operation {
+            Operation::ContrastStretch { .. } => "grayscale",
+            Operation::Harmonize(_) => "accents",
+            _ => "all",
+        };
+        let slots: Vec<usize> = match &stage["slots"] {
+            Yaml::BadValue => slot_group(default).unwrap(),
+            Yaml::Array(list) => list
+                .iter()
+                .flat_map(|x| {
+                    x.as_str()
+                        .and_then(slot_group)
+                        .expect("pipeline.slots: Expected slot names like base08")
+                })
+                .collect(),
+            value => value
+                .as_str()
+                .and_then(slot_group)
+                .expect("pipeline.slots: Expected all, grayscale, accents or a list of slots"),
+        };
+        config.pipeline.push(Stage { operation, slots });
+    }
+}
+
+// Config file keys are the long names of command line options, values given on
+// the command line take precedence
+fn load_config_file(config: &mut Config, path: &str) {
+    let content = fs::read_to_string(path).expect("Could not open config file");
+    let docs = Yaml::load_from_str(&content).expect("Could not load config file as yaml");
+    let Some(entries) = docs.first().and_then(|x| x.as_hash()) else {
+        return;
+    };
+    for (key, value) in entries.iter() {
+        let key = key.as_str().expect("Config keys must be strings");
+        if key == "hooks" {
+            parse_hooks(config, value);
+            continue;
+        }
+        if key == "pipeline" {
+            parse_pipeline(config, value);
+            continue;
+        }
+        let flag = match key {
+            "template" => "-t".to_string(),
+            "depth" => "-d".to_string(),
+            "similarity" => "-s".to_string(),
+            "vibrancy" => "-v".to_string(),
+            "likeness" => "-l".to_string(),
+            _ => format!("--{}", key),
+        };
+        // Lists are the same as repeating the option
+        let values = match value {
+            Yaml::Array(x) => x.iter().collect(),
+            _ => vec![value],
+        };
+        for value in values {
+            let value = match value {
+                Yaml::Real(x) | Yaml::String(x) => x.clone(),
+                Yaml::Integer(x) => x.to_string(),
+                Yaml::Boolean(x) => x.to_string(),
+                _ => panic!("{}: Unsupported value in config file", key),
+            };
+            if !set_option(config, &flag, &value) {
+                eprintln!("Unknown config key: {}", key);
+            }
+        }
+    }
 }
 
 pub fn parse_config(args: Vec<String>) -> Config {
@@ -41,9 +592,30 @@ pub fn parse_config(args: Vec<String>) -> Config {
             (184, 125, 40),
         ],
         depth: 2,
+        center_bias: 0,
+        saliency: 0,
+        masks: Vec::new(),
+        background: None,
+        frames: 0,
+        per_frame: false,
+        format: "yaml".to_string(),
+        histogram: false,
+        auto_tune: false,
+        pipeline: Vec::new(),
+        temperature: None,
+        variants: Vec::new(),
+        svg_size: 1920,
+        exposure: 0.0,
         similarity: 20,
         vibrancy: 15,
         likeness: 20,
+        cvd: Vec::new(),
+        cvd_check: false,
+        cvd_delta: 10,
+        gray_ramp: false,
+        ramp_step: 5,
+        locks: Vec::new(),
+        accent_strategy: AccentStrategy::Frequent,
 
         hue_compare: 0.75,
         chroma_compare: 1.0,
@@ -56,108 +628,38 @@ pub fn parse_config(args: Vec<String>) -> Config {
         hue_tweak: 0,
         saturation_tweak: -1,
         light_tweak: 1,
+
+        hooks: Vec::new(),
+        parallel_hooks: false,
     };
 
+    set_verbosity(&args);
+    if let Some(index) = args.iter().position(|x| x == "--config") {
+        let path = args.get(index + 1).expect("--config: Expected a value");
+        load_config_file(&mut config, &super::handle_path(path.clone()));
+    }
+
     // I feel like this is absolute shit, but it's gonna work like that
-    for entry in args.iter().enumerate().skip(1).step_by(2) {
-        if entry.1 == "-t" {
-            let path = args[entry.0 + 1].clone();
-            if path.starts_with("/") || path.starts_with("\\") {
-                config.template_colors = get_scheme(&path)
-            } else {
-                let current_dir = std::env::current_dir().unwrap().display().to_string();
-                config.template_colors = get_scheme(&format!("{}/{}", current_dir, path));
-            }
-        } else if entry.1 == "-d" {
-            let depth: i64 = args[entry.0 + 1].parse().unwrap();
-            if !(1..=8).contains(&depth) {
-                panic!("-d: Incorrect depth value. Expected 1-8, got {}", depth);
-            } else {
-                config.depth = depth.try_into().expect("Unknown error when parsing depth");
-            }
-        } else if entry.1 == "-s" {
-            config.similarity = args[entry.0 + 1]
-                .parse()
-                .expect("-s: Incorrect variance value. Expected an unsigned 16bit integer");
-        } else if entry.1 == "-v" {
-            let input: i64 = args[entry.0 + 1].parse().unwrap();
-            if !(1..=100).contains(&input) {
-                panic!(
-                    "-c: Incorrect color threshold value. Expected 1-100, got {}",
-                    input
-                );
-            } else {
-                config.vibrancy = input
-                    .try_into()
-                    .expect("Unknown error when parsing color threshold");
-            }
-        } else if entry.1 == "-l" {
-            config.likeness = args[entry.0 + 1]
-                .parse()
-                .expect("-l: Expected an unsigned 16bit integer");
-        } else if entry.1 == "--hue-compare" {
-            let input: f64 = args[entry.0 + 1]
-                .parse()
-                .expect("--hue-compare: Expected a float");
-            if input <= 0.0 {
-                panic!(
-                    "--hue-compare: Incorrect value. Expected a float > 0.0, got {}",
-                    input
-                );
-            } else {
-                config.hue_compare = input;
-            }
-        } else if entry.1 == "--chroma-compare" {
-            let input: f64 = args[entry.0 + 1]
-                .parse()
-                .expect("--chroma-compare: Expected a float");
-            if input <= 0.0 {
-                panic!(
-                    "--chroma-compare: Incorrect value. Expected a float > 0.0, got {}",
-                    input
-                );
-            } else {
-                config.chroma_compare = input;
-            }
-        } else if entry.1 == "--light-compare" {
-            let input: f64 = args[entry.0 + 1]
-                .parse()
-                .expect("--light-compare: Expected a float");
-            if input <= 0.0 {
-                panic!(
-                    "--light-compare: Incorrect value. Expected a float > 0.0, got {}",
-                    input
-                );
-            } else {
-                config.light_compare = input;
-            }
-        } else if entry.1 == "--hue-mix" {
-            config.hue_mix = args[entry.0 + 1]
-                .parse()
-                .expect("--hue-mix: Expected an 8bit integer");
-        } else if entry.1 == "--saturation-mix" {
-            config.saturation_mix = args[entry.0 + 1]
-                .parse()
-                .expect("--saturation-mix: Expected an 8bit integer");
-        } else if entry.1 == "--light-mix" {
-            config.light_mix = args[entry.0 + 1]
-                .parse()
-                .expect("--light-mix: Expected an 8bit integer");
-        } else if entry.1 == "--hue-tweak" {
-            config.hue_tweak = args[entry.0 + 1]
-                .parse()
-                .expect("--hue-tweak: Expected an 8bit integer");
-        } else if entry.1 == "--saturation-tweak" {
-            config.saturation_tweak = args[entry.0 + 1]
-                .parse()
-                .expect("--saturation-tweak: Expected an 8bit integer");
-        } else if entry.1 == "--light-tweak" {
-            config.light_tweak = args[entry.0 + 1]
-                .parse()
-                .expect("--light-tweak: Expected an 8bit integer");
-        } else {
-            eprintln!("Unknown argument: {}", entry.1);
+    let mut index = 1;
+    while index < args.len() {
+        let flag = &args[index];
+        if is_verbosity(&args, index) {
+            index += 1;
+            continue;
+        }
+        // Switches don't take a value on the command line
+        if SWITCHES.contains(&flag.as_str()) {
+            set_option(&mut config, flag, "true");
+            index += 1;
+            continue;
+        }
+        let value = args
+            .get(index + 1)
+            .unwrap_or_else(|| panic!("{}: Expected a value", flag));
+        if flag != "--config" && !set_option(&mut config, flag, value) {
+            eprintln!("Unknown argument: {}", flag);
         }
+        index += 2;
     }
 
     config
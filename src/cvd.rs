@@ -0,0 +1,117 @@
+use std::hash::Hash;
+
+use super::color;
+use super::config::Config;
+use super::data::hex;
+
+#[derive(Clone, Copy, Hash, PartialEq)]
+pub enum Deficiency {
+    Protan,
+    Deutan,
+    Tritan,
+}
+
+impl Deficiency {
+    pub const ALL: [Deficiency; 3] = [Deficiency::Protan, Deficiency::Deutan, Deficiency::Tritan];
+
+    pub fn parse(value: &str) -> Option<Deficiency> {
+        match value {
+            "protan" => Some(Deficiency::Protan),
+            "deutan" => Some(Deficiency::Deutan),
+            "tritan" => Some(Deficiency::Tritan),
+            _ => None,
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Deficiency::Protan => "protan",
+            Deficiency::Deutan => "deutan",
+            Deficiency::Tritan => "tritan",
+        }
+    }
+
+    // Machado, Oliveira and Fernandes (2009) at full severity, for linear RGB
+    fn matrix(&self) -> [[f64; 3]; 3] {
+        match self {
+            Deficiency::Protan => [
+                [0.152286, 1.052583, -0.204868],
+                [0.114503, 0.786281, 0.099216],
+                [-0.003882, -0.048116, 1.051998],
+            ],
+            Deficiency::Deutan => [
+                [0.367322, 0.860646, -0.227968],
+                [0.280085, 0.672501, 0.047413],
+                [-0.011820, 0.042940, 0.968881],
+            ],
+            Deficiency::Tritan => [
+                [1.255528, -0.076749, -0.178779],
+                [-0.078411, 0.930809, 0.147602],
+                [0.004733, 0.691367, 0.303900],
+            ],
+        }
+    }
+}
+
+// How the color looks to someone with the deficiency
+pub fn simulate(rgb: &(u8, u8, u8), deficiency: Deficiency) -> (u8, u8, u8) {
+    let linear = [
+        color::srgb_to_linear(rgb.0),
+        color::srgb_to_linear(rgb.1),
+        color::srgb_to_linear(rgb.2),
+    ];
+    let m = deficiency.matrix();
+    let channel = |row: [f64; 3]| {
+        color::linear_to_srgb(row[0] * linear[0] + row[1] * linear[1] + row[2] * linear[2])
+    };
+    (channel(m[0]), channel(m[1]), channel(m[2]))
+}
+
+// Smallest distance between the colors as seen with normal vision or any of the
+// deficiencies in config.cvd, what de-duplication of the palette goes by
+pub fn distance(c1: &(u8, u8, u8), c2: &(u8, u8, u8), config: &Config) -> u16 {
+    config
+        .cvd
+        .iter()
+        .map(|x| color::compare_colors(&simulate(c1, *x), &simulate(c2, *x), 1.0, 1.0, 1.0))
+        .fold(color::compare_colors(c1, c2, 1.0, 1.0, 1.0), u16::min)
+}
+
+// Warns about every pair of base08-base0F that gets closer than config.cvd_delta under
+// any of the simulations, returns how many pairs failed
+pub fn check(palette: &[Option<(u8, u8, u8)>], config: &Config) -> usize {
+    let mut failures = 0;
+    for deficiency in Deficiency::ALL {
+        for i in 8..16 {
+            for j in i + 1..16 {
+                let (Some(c1), Some(c2)) = (palette[i], palette[j]) else {
+                    continue;
+                };
+                let (s1, s2) = (simulate(&c1, deficiency), simulate(&c2, deficiency));
+                let distance = color::compare_colors(&s1, &s2, 1.0, 1.0, 1.0);
+                if distance < config.cvd_delta {
+                    eprintln!(
+                        "{}: base{:02X} {} and base{:02X} {} look like {} and {}, ΔE {} is under {}",
+                        deficiency.name(),
+                        i,
+                        hex(&c1),
+                        j,
+                        hex(&c2),
+                        hex(&s1),
+                        hex(&s2),
+                        distance,
+                        config.cvd_delta
+                    );
+                    failures += 1;
+                }
+            }
+        }
+    }
+    if failures == 0 {
+        info!(
+            "Accent colors stay distinguishable for protan, deutan and tritan vision (ΔE >= {})",
+            config.cvd_delta
+        );
+    }
+    failures
+}
@@ -1,23 +1,188 @@
-use std::collections::{BinaryHeap, HashMap};
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
 
 use super::color;
 use super::config::Config;
+use super::cvd;
 use super::quantize::ColorCount;
 
-pub fn get_accent(input: &mut BinaryHeap<ColorCount>, threshold: u8) -> (u8, u8, u8) {
-    let mut colors = input.clone();
+pub type Palette = Vec<Option<(u8, u8, u8)>>;
+// Candidate colors of every slot, by slot index since template colors can repeat
+pub type Candidates = Vec<BinaryHeap<ColorCount>>;
 
-    colors.retain(|x| color::is_colorful(&x.rgb, threshold));
-    let accent = colors.pop().unwrap().rgb;
-    input.retain(|x| x.rgb.0 != accent.0 && x.rgb.1 != accent.1 && x.rgb.2 != accent.2);
-    accent
+// What happened to one slot while the palette was built, for reewal inspect
+#[derive(Default)]
+pub struct SlotReport {
+    // Extracted colors within likeness of the template color
+    pub candidates: usize,
+    pub chosen: Option<ColorCount>,
+    pub evicted: Vec<Eviction>,
+    pub generated: bool,
+    pub locked: bool,
+    pub tweaks: u16,
+}
+
+// A candidate dropped by check_and_replace for being too similar to another slot
+pub struct Eviction {
+    pub color: ColorCount,
+    pub conflict: usize,
+    pub conflict_rgb: (u8, u8, u8),
+    pub distance: u16,
+}
+
+pub enum AccentStrategy {
+    Frequent,
+    Saturated,
+    Score,
+    Center,
+    Hint((u8, u8, u8)),
+    Complementary,
+}
+
+pub fn hex(rgb: &(u8, u8, u8)) -> String {
+    format!("#{:02X}{:02X}{:02X}", rgb.0, rgb.1, rgb.2)
 }
 
-pub fn map_colors(
-    candidate_colors: BinaryHeap<ColorCount>,
+// Ties go to the more frequent color, so the order colors come in doesn't matter
+fn nearest(colors: &[ColorCount], target: &(u8, u8, u8)) -> Option<ColorCount> {
+    colors
+        .iter()
+        .min_by_key(|x| {
+            (
+                color::compare_colors(&x.rgb, target, 1.0, 1.0, 1.0),
+                Reverse((x.count, x.rgb)),
+            )
+        })
+        .cloned()
+}
+
+// Most frequent colorful cluster, the one that gives the wallpaper its tint, or the
+// most frequent one when nothing is colorful
+pub fn dominant(input: &BinaryHeap<ColorCount>, config: &Config) -> (u8, u8, u8) {
+    input
+        .iter()
+        .filter(|x| color::is_colorful(&x.rgb, config.vibrancy))
+        .max()
+        .or(input.peek())
+        .map_or((0, 0, 0), |x| x.rgb)
+}
+
+// Returns the accent with an explanation of why it was picked, the center strategy
+// needs the histogram quantized with quantize::center_weight
+pub fn get_accent(
+    input: &mut BinaryHeap<ColorCount>,
+    centered: Option<&BinaryHeap<ColorCount>>,
     config: &Config,
-) -> HashMap<(u8, u8, u8), BinaryHeap<ColorCount>> {
-    let mut color_map: HashMap<(u8, u8, u8), BinaryHeap<ColorCount>> = HashMap::new();
+) -> ((u8, u8, u8), String) {
+    let threshold = config.vibrancy;
+    let mut colors: Vec<ColorCount> = input
+        .iter()
+        .filter(|x| color::is_colorful(&x.rgb, threshold))
+        .cloned()
+        .collect();
+    colors.sort_by(|a, b| b.cmp(a));
+    if colors.is_empty() {
+        panic!("No colorful enough colors in the image to pick an accent from");
+    }
+
+    let (accent, reason) = match &config.accent_strategy {
+        AccentStrategy::Frequent => (
+            colors[0].rgb,
+            format!("most frequent colorful cluster ({} px)", colors[0].count),
+        ),
+        AccentStrategy::Saturated => {
+            let best = colors
+                .iter()
+                .max_by(|a, b| color::get_chroma(&a.rgb).total_cmp(&color::get_chroma(&b.rgb)))
+                .unwrap();
+            (
+                best.rgb,
+                format!(
+                    "highest chroma {:.1} ({} px)",
+                    color::get_chroma(&best.rgb),
+                    best.count
+                ),
+            )
+        }
+        AccentStrategy::Score => {
+            let score = |x: &ColorCount| color::get_chroma(&x.rgb) * x.count as f64;
+            let best = colors
+                .iter()
+                .max_by(|a, b| score(a).total_cmp(&score(b)))
+                .unwrap();
+            (
+                best.rgb,
+                format!(
+                    "highest chroma x frequency score {:.0} (chroma {:.1}, {} px)",
+                    score(best),
+                    color::get_chroma(&best.rgb),
+                    best.count
+                ),
+            )
+        }
+        AccentStrategy::Center => {
+            let centered = centered.expect("Center accent strategy needs a centered histogram");
+            let center = centered
+                .iter()
+                .filter(|x| color::is_colorful(&x.rgb, threshold))
+                .max()
+                .expect("No colorful enough colors near the image center");
+            let best = nearest(&colors, &center.rgb).unwrap();
+            (
+                best.rgb,
+                format!(
+                    "most frequent colorful cluster near the center {} (weight {}), nearest extracted color ({} px)",
+                    hex(&center.rgb),
+                    center.count,
+                    best.count
+                ),
+            )
+        }
+        AccentStrategy::Hint(hint) => {
+            let all: Vec<ColorCount> = input.iter().cloned().collect();
+            let best = nearest(&all, hint).unwrap();
+            (
+                best.rgb,
+                format!(
+                    "nearest extracted color to hint {} (distance {}, {} px)",
+                    hex(hint),
+                    color::compare_colors(&best.rgb, hint, 1.0, 1.0, 1.0),
+                    best.count
+                ),
+            )
+        }
+        AccentStrategy::Complementary => {
+            // Most frequent colorful cluster, the hue of a gray or near black means nothing
+            let dominant = &colors[0];
+            let target = (color::get_hue(&dominant.rgb) + 180.0) % 360.0;
+            let hue_distance = |x: &ColorCount| {
+                let d = (color::get_hue(&x.rgb) - target).abs();
+                d.min(360.0 - d)
+            };
+            // colors are sorted by count, so ties go to the more frequent one
+            let best = colors
+                .iter()
+                .min_by(|a, b| hue_distance(a).total_cmp(&hue_distance(b)))
+                .unwrap();
+            (
+                best.rgb,
+                format!(
+                    "closest hue to {:.0}, complementary to dominant {} ({:.0} off, {} px)",
+                    target,
+                    hex(&dominant.rgb),
+                    hue_distance(best),
+                    best.count
+                ),
+            )
+        }
+    };
+
+    input.retain(|x| x.rgb.0 != accent.0 && x.rgb.1 != accent.1 && x.rgb.2 != accent.2);
+    (accent, reason)
+}
+
+pub fn map_colors(candidate_colors: BinaryHeap<ColorCount>, config: &Config) -> Candidates {
+    let mut color_map: Candidates = Vec::new();
     let template_colors = &config.template_colors;
 
     for color in template_colors.iter().enumerate() {
@@ -36,56 +201,193 @@ pub fn map_colors(
                 heap.push(candidate.clone());
             }
         }
-        color_map.insert(*color.1, heap);
+        debug!(
+            "base{:02X} {}: {} candidates",
+            color.0,
+            hex(color.1),
+            heap.len()
+        );
+        color_map.push(heap);
     }
     color_map
 }
 
 fn assign_grayscale_colors(
-    color_map: &HashMap<(u8, u8, u8), BinaryHeap<ColorCount>>,
-    template_colors: &Vec<(u8, u8, u8)>,
+    color_map: &Candidates,
+    template_colors: &[(u8, u8, u8)],
     config: &Config,
+    reports: &mut [SlotReport],
 ) -> Vec<Option<(u8, u8, u8)>> {
     let mut output: Vec<Option<(u8, u8, u8)>> = vec![None; 16];
     for i in 0..8 {
+        if config.locked(i).is_some() {
+            continue;
+        }
         let color = &template_colors[i];
-        let mut best_color = None;
-        let mut best_distance = u16::MAX;
+        // Ties go to the more frequent candidate, heap iteration order isn't stable
+        let best_color = color_map[i].iter().min_by_key(|candidate| {
+            (
+                color::compare_colors(
+                    &candidate.rgb,
+                    color,
+                    2.0 * config.hue_compare, // We're comparing grayscale colors, hue shouldn't matter
+                    config.chroma_compare,
+                    0.9 * config.light_compare, // and this should matter more i think
+                ),
+                Reverse((candidate.count, candidate.rgb)),
+            )
+        });
+        output[i] = best_color.map(|x| x.rgb);
+        reports[i].chosen = best_color.cloned();
+    }
+    output
+}
 
-        for candidate in color_map.get(color).unwrap().iter() {
-            let candidate_distance = color::compare_colors(
-                &candidate.rgb,
-                color,
-                2.0 * config.hue_compare, // We're comparing grayscale colors, hue shouldn't matter
-                config.chroma_compare,
-                0.9 * config.light_compare, // and this should matter more i think
-            );
-            if candidate_distance < best_distance {
-                best_distance = candidate_distance;
-                best_color = Some(candidate.rgb);
+fn evict(
+    reports: &mut [SlotReport],
+    index: usize,
+    conflict: usize,
+    conflict_rgb: (u8, u8, u8),
+    distance: u16,
+) {
+    if let Some(color) = reports[index].chosen.take() {
+        reports[index].evicted.push(Eviction {
+            color,
+            conflict,
+            conflict_rgb,
+            distance,
+        });
+    }
+}
+
+// Highest OKLCH chroma a step of the ramp keeps, so it still reads as gray
+const RAMP_CHROMA: f64 = 0.06;
+// Tint of steps when the image has no grayscale candidates at all
+const RAMP_TINT: f64 = 0.02;
+
+// Chroma and hue for a step without a candidate, interpolated between the closest
+// steps on both sides that have one
+fn ramp_tint(tints: &[Option<(f64, f64)>], index: usize, dominant: f64) -> (f64, f64) {
+    let below = (0..index).rev().find_map(|x| tints[x].map(|t| (x, t)));
+    let above = (index + 1..tints.len()).find_map(|x| tints[x].map(|t| (x, t)));
+    match (below, above) {
+        (Some((i, a)), Some((j, b))) => {
+            let t = (index - i) as f64 / (j - i) as f64;
+            let arc = (b.1 - a.1 + 540.0).rem_euclid(360.0) - 180.0;
+            (a.0 + (b.0 - a.0) * t, (a.1 + arc * t).rem_euclid(360.0))
+        }
+        (Some((_, x)), None) | (None, Some((_, x))) => x,
+        (None, None) => (RAMP_TINT, dominant),
+    }
+}
+
+// Replaces base00-base07 by a ramp with evenly spaced lightness, from the base00 to
+// the base07 color found (or the template's darkest and lightest, when there are
+// none), at least config.ramp_step apart. Locked slots keep their color and the ramp
+// is spaced evenly between them. Steps keep the tint of their candidate, the ones
+// without get it interpolated from their unlocked neighbours or the dominant hue
+fn grayscale_ramp(
+    palette: &mut [Option<(u8, u8, u8)>],
+    dominant: &(u8, u8, u8),
+    config: &Config,
+    reports: &mut [SlotReport],
+) {
+    let lch: Vec<Option<(f64, f64, f64)>> = palette[0..8]
+        .iter()
+        .map(|x| x.map(|x| color::rgb_to_oklch(&x)))
+        .collect();
+    let template: Vec<f64> = config.template_colors[0..8]
+        .iter()
+        .map(|x| color::rgb_to_oklch(x).0)
+        .collect();
+    let mut start = lch[0].map_or(template[0], |x| x.0);
+    let mut end = lch[7].map_or(template.iter().cloned().fold(0.0, f64::max), |x| x.0);
+    let step = config.ramp_step as f64 / 100.0;
+    if end - start < 7.0 * step {
+        end = (start + 7.0 * step).min(1.0);
+        start = end - 7.0 * step;
+    }
+
+    // Lightness the ramp has to go through, the ends and every locked slot
+    let locked = |i: usize| config.locked(i).is_some();
+    let mut anchors: Vec<(usize, f64)> = Vec::new();
+    for (i, x) in lch.iter().enumerate() {
+        match x {
+            Some(x) if locked(i) => anchors.push((i, x.0)),
+            _ if i == 0 => anchors.push((0, start)),
+            _ if i == 7 => anchors.push((7, end)),
+            _ => {}
+        }
+    }
+    let lightness: Vec<f64> = (0..8)
+        .map(|i| {
+            let below = anchors.iter().rev().find(|x| x.0 <= i).unwrap();
+            let above = anchors.iter().find(|x| x.0 >= i).unwrap();
+            match above.0 - below.0 {
+                0 => below.1,
+                span => below.1 + (above.1 - below.1) * (i - below.0) as f64 / span as f64,
             }
+        })
+        .collect();
+    for i in 1..8 {
+        if (locked(i) || locked(i - 1)) && lightness[i] - lightness[i - 1] < step - 0.005 {
+            eprintln!(
+                "--lock: base{:02X} at lightness {:.2} and base{:02X} at {:.2} break the grayscale ramp, steps should go up by at least {:.2}",
+                i - 1,
+                lightness[i - 1],
+                i,
+                lightness[i],
+                step
+            );
         }
-        output[i] = best_color;
     }
-    output
+
+    // Locked colors are the user's, not the wallpaper's, they don't tint their neighbours
+    let tints: Vec<Option<(f64, f64)>> = lch
+        .iter()
+        .enumerate()
+        .map(|(i, x)| {
+            x.filter(|_| !locked(i))
+                .map(|x| (x.1.min(RAMP_CHROMA), x.2))
+        })
+        .collect();
+    let dominant = color::rgb_to_oklch(dominant).2;
+    for i in 0..8 {
+        if locked(i) {
+            continue;
+        }
+        let (chroma, hue) = tints[i].unwrap_or_else(|| ramp_tint(&tints, i, dominant));
+        palette[i] = Some(color::oklch_to_rgb(&(lightness[i], chroma, hue)));
+        if tints[i].is_none() {
+            reports[i].generated = true;
+        }
+    }
+    debug!(
+        "Grayscale ramp from lightness {:.2} to {:.2}, tinted toward hue {:.0}",
+        lightness[0], lightness[7], dominant
+    );
 }
 
-// Recursive deletion and replacement until all colors are distinct enough
+// Recursive deletion and replacement until all colors are distinct enough, the
+// accent and locked colors are never the ones removed
 fn check_and_replace(
-    color_map: &mut HashMap<(u8, u8, u8), BinaryHeap<ColorCount>>,
+    color_map: &mut Candidates,
     palette: &mut Vec<Option<(u8, u8, u8)>>,
-    template_colors: &Vec<(u8, u8, u8)>,
+    template_colors: &[(u8, u8, u8)],
     config: &Config,
+    reports: &mut [SlotReport],
     index: usize,
 ) {
-    if index == 13 {
+    let fixed = |x: usize| x == 13 || config.locked(x).is_some();
+    if fixed(index) {
         return;
     }
     if let Some(c1) = palette[index] {
         for i in 8..16 {
             if i != index {
                 if let Some(c2) = palette[i] {
-                    if color::compare_colors(&c1, &c2, 1.0, 1.0, 1.0) < config.similarity {
+                    let distance = cvd::distance(&c1, &c2, config);
+                    if distance < config.similarity {
                         // Remove the color that is less similar to the template color
                         let c1_distance = color::compare_colors(
                             &c1,
@@ -101,31 +403,55 @@ fn check_and_replace(
                             config.chroma_compare,
                             config.light_compare,
                         );
-                        if c1_distance > c2_distance || i == 13 {
-                            if let Some(next_candidate) =
-                                color_map.get_mut(&template_colors[index]).unwrap().pop()
-                            {
+                        if c1_distance > c2_distance || fixed(i) {
+                            debug!(
+                                "base{:02X}: rejected {}, too close to base{:02X} {}",
+                                index,
+                                hex(&c1),
+                                i,
+                                hex(&c2)
+                            );
+                            evict(reports, index, i, c2, distance);
+                            if let Some(next_candidate) = color_map[index].pop() {
                                 palette[index] = Some(next_candidate.rgb);
+                                reports[index].chosen = Some(next_candidate);
                                 // Recheck against the entire palette
                                 check_and_replace(
                                     color_map,
                                     palette,
                                     template_colors,
                                     config,
+                                    reports,
                                     index,
                                 );
                             } else {
                                 palette[index] = None; // mark as removed
                             }
-                        } else if let Some(next_candidate) =
-                            color_map.get_mut(&template_colors[i]).unwrap().pop()
-                        {
-                            palette[i] = Some(next_candidate.rgb);
-                            // Recheck against the entire palette
-                            check_and_replace(color_map, palette, template_colors, config, i);
                         } else {
-                            // If no more candidate colors, leave it as is
-                            palette[i] = None;
+                            debug!(
+                                "base{:02X}: rejected {}, too close to base{:02X} {}",
+                                i,
+                                hex(&c2),
+                                index,
+                                hex(&c1)
+                            );
+                            evict(reports, i, index, c1, distance);
+                            if let Some(next_candidate) = color_map[i].pop() {
+                                palette[i] = Some(next_candidate.rgb);
+                                reports[i].chosen = Some(next_candidate);
+                                // Recheck against the entire palette
+                                check_and_replace(
+                                    color_map,
+                                    palette,
+                                    template_colors,
+                                    config,
+                                    reports,
+                                    i,
+                                );
+                            } else {
+                                // If no more candidate colors, leave it as is
+                                palette[i] = None;
+                            }
                         }
                     }
                 }
@@ -135,12 +461,12 @@ fn check_and_replace(
 }
 
 fn gen_color(
-    palette: &Vec<Option<(u8, u8, u8)>>,
-    template_colors: &Vec<(u8, u8, u8)>,
+    palette: &[Option<(u8, u8, u8)>],
+    template_colors: &[(u8, u8, u8)],
     accent: &(u8, u8, u8),
     config: &Config,
     index: usize,
-) -> (u8, u8, u8) {
+) -> ((u8, u8, u8), u16) {
     let mut generated = color::mix_colors(
         &template_colors[index],
         accent,
@@ -156,18 +482,21 @@ fn gen_color(
         config.light_compare,
     );
     let mut palette_distance = u16::MAX;
-    for i in 8..16 {
-        if let Some(c) = palette[i] {
-            let temp = color::compare_colors(
-                &generated,
-                &c,
-                config.hue_compare,
-                config.chroma_compare,
-                config.light_compare,
-            );
-            if temp < palette_distance {
-                palette_distance = temp;
-            }
+    for c in palette[8..16].iter().flatten() {
+        let temp = color::compare_colors(
+            &generated,
+            c,
+            config.hue_compare,
+            config.chroma_compare,
+            config.light_compare,
+        );
+        // Generated colors should stay apart for the simulated vision too
+        let temp = match config.cvd.is_empty() {
+            true => temp,
+            false => temp.min(cvd::distance(&generated, c, config)),
+        };
+        if temp < palette_distance {
+            palette_distance = temp;
         }
     }
     let mut best_distance = palette_distance;
@@ -188,18 +517,21 @@ fn gen_color(
             config.light_compare,
         );
         palette_distance = u16::MAX;
-        for i in 8..16 {
-            if let Some(c) = palette[i] {
-                let temp = color::compare_colors(
-                    &generated,
-                    &c,
-                    config.hue_compare,
-                    config.chroma_compare,
-                    config.light_compare,
-                );
-                if temp < palette_distance {
-                    palette_distance = temp;
-                }
+        for c in palette[8..16].iter().flatten() {
+            let temp = color::compare_colors(
+                &generated,
+                c,
+                config.hue_compare,
+                config.chroma_compare,
+                config.light_compare,
+            );
+            // Generated colors should stay apart for the simulated vision too
+            let temp = match config.cvd.is_empty() {
+                true => temp,
+                false => temp.min(cvd::distance(&generated, c, config)),
+            };
+            if temp < palette_distance {
+                palette_distance = temp;
             }
         }
         if palette_distance > best_distance {
@@ -208,45 +540,83 @@ fn gen_color(
         }
         i += 1;
     }
-    best_color
+    (best_color, i)
 }
 
 pub fn create_palette(
-    color_map: &mut HashMap<(u8, u8, u8), BinaryHeap<ColorCount>>,
+    color_map: &mut Candidates,
     accent_color: (u8, u8, u8),
+    dominant: (u8, u8, u8),
     config: &Config,
 ) -> Vec<Option<(u8, u8, u8)>> {
+    explain_palette(color_map, accent_color, dominant, config).0
+}
+
+// create_palette along with a report of how every slot got its color, the dominant
+// color tints the grayscale ramp
+pub fn explain_palette(
+    color_map: &mut Candidates,
+    accent_color: (u8, u8, u8),
+    dominant: (u8, u8, u8),
+    config: &Config,
+) -> (Palette, Vec<SlotReport>) {
     let template_colors = &config.template_colors;
-    let mut palette = assign_grayscale_colors(color_map, template_colors, config);
+    let mut reports: Vec<SlotReport> = color_map
+        .iter()
+        .map(|x| SlotReport {
+            candidates: x.len(),
+            ..Default::default()
+        })
+        .collect();
+    // Locking base0D replaces the accent, generated colors are mixed with it too
+    let accent_color = config.locked(13).unwrap_or(accent_color);
+    let mut palette = assign_grayscale_colors(color_map, template_colors, config, &mut reports);
+    // Locked colors are in place before anything is extracted for the other slots,
+    // so the ramp and the distinctness checks work around them
+    for (index, rgb) in &config.locks {
+        palette[*index] = Some(*rgb);
+        reports[*index].locked = true;
+        debug!("base{:02X}: locked to {}", index, hex(rgb));
+    }
+    if config.gray_ramp {
+        grayscale_ramp(&mut palette, &dominant, config, &mut reports);
+    }
     palette[13] = Some(accent_color);
 
     // Assign most frequent candidate colors to each slot
     for i in 8..16 {
-        if i != 13 {
-            palette[i] = color_map
-                .get_mut(&template_colors[i])
-                .unwrap()
-                .pop()
-                .map(|x| x.rgb);
+        if i != 13 && config.locked(i).is_none() {
+            reports[i].chosen = color_map[i].pop();
+            palette[i] = reports[i].chosen.as_ref().map(|x| x.rgb);
         }
     }
 
     // Remove colors that are too similar to each other
     for i in 8..16 {
-        check_and_replace(color_map, &mut palette, template_colors, config, i);
+        check_and_replace(
+            color_map,
+            &mut palette,
+            template_colors,
+            config,
+            &mut reports,
+            i,
+        );
     }
 
     for i in 8..16 {
         if palette[i].is_none() {
-            palette[i] = Some(gen_color(
-                &palette,
-                template_colors,
-                &accent_color,
-                config,
+            let (generated, tweaks) =
+                gen_color(&palette, template_colors, &accent_color, config, i);
+            palette[i] = Some(generated);
+            reports[i].generated = true;
+            reports[i].tweaks = tweaks;
+            debug!(
+                "base{:02X}: no candidates left, generated {}",
                 i,
-            ));
+                hex(&generated)
+            );
         }
     }
 
-    palette
+    (palette, reports)
 }
@@ -0,0 +1,129 @@
+use std::env;
+use std::io::{self, IsTerminal};
+use std::process;
+
+use super::color;
+use super::config;
+use super::data::{hex, Palette};
+use super::yaml;
+
+// Swatches only when they end up on a terminal, NO_COLOR turns them off
+fn use_color() -> bool {
+    io::stdout().is_terminal() && env::var_os("NO_COLOR").is_none()
+}
+
+fn swatch(rgb: &Option<(u8, u8, u8)>, colored: bool) -> String {
+    let name = rgb.map_or("-".to_string(), |x| hex(&x));
+    match rgb {
+        Some(c) if colored => format!("\x1b[48;2;{};{};{}m    \x1b[0m {:<7}", c.0, c.1, c.2, name),
+        _ if colored => format!("     {:<7}", name),
+        _ => format!("{:<7}", name),
+    }
+}
+
+// Prints both schemes side by side with the distance of every slot, returns the
+// largest distance or None when a slot is missing from either
+fn compare(
+    a: &[Option<(u8, u8, u8)>],
+    b: &[Option<(u8, u8, u8)>],
+    names: (&str, &str),
+) -> Option<u16> {
+    let colored = use_color();
+    let width = if colored { 12 } else { 7 };
+    println!(
+        "{:<6}  {:<width$}  {:<width$}  ΔE",
+        "Slot", names.0, names.1
+    );
+    let mut distances = Vec::new();
+    let mut missing = 0;
+    for index in 0..16 {
+        let distance = match (a[index], b[index]) {
+            (Some(c1), Some(c2)) => {
+                let distance = color::compare_colors(&c1, &c2, 1.0, 1.0, 1.0);
+                distances.push((index, distance));
+                distance.to_string()
+            }
+            _ => {
+                missing += 1;
+                "-".to_string()
+            }
+        };
+        println!(
+            "base{:02X}  {}  {}  {}",
+            index,
+            swatch(&a[index], colored),
+            swatch(&b[index], colored),
+            distance
+        );
+    }
+
+    let identical = distances.iter().filter(|x| x.1 == 0).count();
+    let mean = distances.iter().map(|x| x.1 as f64).sum::<f64>() / distances.len().max(1) as f64;
+    let max = distances.iter().max_by_key(|x| x.1);
+    println!();
+    print!("{} of 16 slots identical, mean ΔE {:.1}", identical, mean);
+    if let Some((index, distance)) = max {
+        print!(", max ΔE {} at base{:02X}", distance, index);
+    }
+    if missing > 0 {
+        print!(", {} missing", missing);
+    }
+    println!();
+    match missing {
+        0 => Some(max.map_or(0, |x| x.1)),
+        _ => None,
+    }
+}
+
+fn load(path: String) -> Palette {
+    yaml::read_scheme(&super::handle_path(path))
+}
+
+// reewal diff a.yaml b.yaml, or with --against reference.yaml the palette generated
+// from an image against the reference. --max fails when any slot is further apart
+pub fn run(mut args: Vec<String>) {
+    let against = config::take_flag(&mut args, "--against");
+    let max: Option<u16> = config::take_flag(&mut args, "--max").map(|x| {
+        x.parse()
+            .expect("--max: Expected an unsigned 16bit integer")
+    });
+
+    let largest = match against {
+        Some(reference) => {
+            if args.len() < 2 {
+                panic!("Insufficient arguments provided. An image location required");
+            }
+            let image = args.pop().expect("No image provided");
+            let config = config::parse_config(args);
+            let bytes = super::read_image(image);
+            let (generated, _) = super::create_scheme(&bytes, &config);
+            compare(&generated, &load(reference), ("Generated", "Reference"))
+        }
+        None => {
+            if args.len() < 3 {
+                panic!("Insufficient arguments provided. Two scheme locations required");
+            }
+            let b = args.pop().expect("No second scheme provided");
+            let a = args.pop().expect("No first scheme provided");
+            config::set_verbosity(&args);
+            compare(&load(a), &load(b), ("A", "B"))
+        }
+    };
+
+    if let Some(max) = max {
+        match largest {
+            Some(largest) if largest <= max => {}
+            Some(largest) => {
+                eprintln!(
+                    "Schemes differ by up to ΔE {}, more than --max {}",
+                    largest, max
+                );
+                process::exit(1);
+            }
+            None => {
+                eprintln!("Schemes don't have the same slots");
+                process::exit(1);
+            }
+        }
+    }
+}
@@ -0,0 +1,245 @@
+use exif::{In, Tag};
+use image::{DynamicImage, ImageBuffer, ImageDecoder, ImageFormat, ImageReader, Rgba, RgbaImage};
+use resvg::{tiny_skia, usvg};
+use std::env;
+use std::fs::{self, DirBuilder};
+use std::io::{Cursor, ErrorKind};
+use std::os::unix::fs::DirBuilderExt;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use super::config::Config;
+use super::hash::hash_bytes;
+
+fn is_svg(bytes: &[u8]) -> bool {
+    let start = &bytes[..bytes.len().min(1024)];
+    let text = String::from_utf8_lossy(start);
+    let text = text.trim_start_matches('\u{feff}').trim_start();
+    (text.starts_with("<?xml") || text.starts_with("<svg") || text.starts_with("<!--"))
+        && text.contains("<svg")
+}
+
+fn is_jxl(bytes: &[u8]) -> bool {
+    bytes.starts_with(&[0xFF, 0x0A])
+        || bytes.starts_with(&[
+            0x00, 0x00, 0x00, 0x0C, 0x4A, 0x58, 0x4C, 0x20, 0x0D, 0x0A, 0x87, 0x0A,
+        ])
+}
+
+// Rendered so the longer side is `size` pixels
+fn rasterize_svg(bytes: &[u8], size: u32) -> DynamicImage {
+    let tree =
+        usvg::Tree::from_data(bytes, &usvg::Options::default()).expect("Could not parse svg image");
+    let svg_size = tree.size();
+    let scale = size as f32 / svg_size.width().max(svg_size.height());
+    let width = (svg_size.width() * scale).round().max(1.0) as u32;
+    let height = (svg_size.height() * scale).round().max(1.0) as u32;
+    let mut pixmap = tiny_skia::Pixmap::new(width, height).expect("Could not allocate svg image");
+    resvg::render(
+        &tree,
+        tiny_skia::Transform::from_scale(scale, scale),
+        &mut pixmap.as_mut(),
+    );
+
+    // tiny-skia keeps premultiplied alpha
+    let mut img = RgbaImage::new(width, height);
+    for (pixel, color) in img.pixels_mut().zip(pixmap.pixels()) {
+        let color = color.demultiply();
+        *pixel = Rgba([color.red(), color.green(), color.blue(), color.alpha()]);
+    }
+    DynamicImage::ImageRgba8(img)
+}
+
+// Private directory for the files of one external decode. Creating it fails when
+// anything, a symlink included, already has the name, so another user of a shared
+// temporary directory can't point us at their files
+fn private_dir(bytes: &[u8]) -> PathBuf {
+    let mut builder = DirBuilder::new();
+    builder.mode(0o700);
+    for attempt in 0u32.. {
+        let dir = env::temp_dir().join(format!(
+            "reewal-{}-{:016x}-{}",
+            std::process::id(),
+            hash_bytes(bytes),
+            attempt
+        ));
+        match builder.create(&dir) {
+            Ok(()) => return dir,
+            Err(e) if e.kind() == ErrorKind::AlreadyExists && attempt < 100 => continue,
+            Err(e) => panic!("Could not create temporary directory: {}", e),
+        }
+    }
+    unreachable!()
+}
+
+// There's no pure rust decoder for these, so go through the reference tools
+// (djxl from libjxl, avifdec from libavif) and read back the png they write
+fn decode_external(bytes: &[u8], tool: &str, extension: &str) -> DynamicImage {
+    let dir = private_dir(bytes);
+    let input = dir.join(format!("input.{}", extension));
+    let output = dir.join("output.png");
+    let written = fs::write(&input, bytes);
+    let status = written.map(|_| Command::new(tool).arg(&input).arg(&output).output());
+    let decoded = fs::read(&output);
+    let _ = fs::remove_dir_all(&dir);
+    match status.expect("Could not write temporary image") {
+        Ok(status) if status.status.success() => {}
+        Ok(status) => panic!(
+            "{} could not decode the image: {}",
+            tool,
+            String::from_utf8_lossy(&status.stderr).trim()
+        ),
+        Err(e) => panic!(
+            "Decoding {} images needs {} installed: {}",
+            extension, tool, e
+        ),
+    }
+    // The png keeps the profile and orientation of the original, so phone photos in
+    // P3 get converted like any other image
+    decode_tagged(&decoded.expect("Could not read decoded image"))
+}
+
+fn srgb_encode(linear: f32) -> f32 {
+    if linear <= 0.0031308 {
+        12.92 * linear
+    } else {
+        1.055 * linear.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+// ACES filmic curve fit by Krzysztof Narkowicz
+fn aces(x: f32) -> f32 {
+    ((x * (2.51 * x + 0.03)) / (x * (2.43 * x + 0.59) + 0.14)).clamp(0.0, 1.0)
+}
+
+// Float images (HDR, EXR) hold linear light that can go way past 1.0, tone map it
+// into 16 bit sRGB instead of letting the conversion clip it
+fn tone_map(img: DynamicImage, exposure: f64) -> DynamicImage {
+    if !matches!(
+        img,
+        DynamicImage::ImageRgb32F(_) | DynamicImage::ImageRgba32F(_)
+    ) {
+        return img;
+    }
+    let gain = 2f32.powf(exposure as f32);
+    let linear = img.into_rgba32f();
+    let mapped = ImageBuffer::from_fn(linear.width(), linear.height(), |x, y| {
+        let pixel = linear.get_pixel(x, y).0;
+        let channel = |v: f32| (srgb_encode(aces(v.max(0.0) * gain)) * 65535.0).round() as u16;
+        Rgba([
+            channel(pixel[0]),
+            channel(pixel[1]),
+            channel(pixel[2]),
+            (pixel[3].clamp(0.0, 1.0) * 65535.0).round() as u16,
+        ])
+    });
+    DynamicImage::ImageRgba16(mapped)
+}
+
+// Adobe RGB (1998), cameras mark it in EXIF instead of embedding a profile
+fn adobe_rgb() -> Option<Box<qcms::Profile>> {
+    let xy = |x, y| qcms::CIE_xyY { x, y, Y: 1.0 };
+    qcms::Profile::new_rgb_with_gamma_set(
+        xy(0.3127, 0.3290),
+        qcms::CIE_xyYTRIPLE {
+            red: xy(0.64, 0.33),
+            green: xy(0.21, 0.71),
+            blue: xy(0.15, 0.06),
+        },
+        2.2,
+        2.2,
+        2.2,
+    )
+}
+
+// DCF marks Adobe RGB as an uncalibrated color space with the R03 interoperability
+// index, some cameras write color space 2 instead
+fn exif_is_adobe_rgb(exif: Vec<u8>) -> bool {
+    let Ok(exif) = exif::Reader::new().read_raw(exif) else {
+        return false;
+    };
+    let color_space = exif
+        .get_field(Tag::ColorSpace, In::PRIMARY)
+        .and_then(|x| x.value.get_uint(0));
+    let interop = exif
+        .get_field(Tag::InteroperabilityIndex, In::PRIMARY)
+        .map(|x| x.display_value().to_string())
+        .unwrap_or_default();
+    color_space == Some(2) || (color_space == Some(0xFFFF) && interop.contains("R03"))
+}
+
+// qcms only transforms 8 bit data, so images with deeper channels lose their extra
+// precision here
+fn to_srgb(img: DynamicImage, profile: &qcms::Profile) -> DynamicImage {
+    if profile.is_sRGB() {
+        return img;
+    }
+    let srgb = qcms::Profile::new_sRGB();
+    let Some(transform) = qcms::Transform::new(
+        profile,
+        &srgb,
+        qcms::DataType::RGBA8,
+        qcms::Intent::default(),
+    ) else {
+        eprintln!("Unsupported color profile, using the image as sRGB");
+        return img;
+    };
+    let color = img.color();
+    if color.bits_per_pixel() > 8 * color.channel_count() as u16 {
+        info!("Converting the color profile reduces the image to 8 bits per channel");
+    }
+    let mut rgba = img.into_rgba8();
+    transform.apply(&mut rgba);
+    DynamicImage::ImageRgba8(rgba)
+}
+
+// Orientation from EXIF is applied and colors are converted to sRGB from the embedded
+// ICC profile, or Adobe RGB when EXIF says so, so colors match what viewers show
+fn decode_tagged(bytes: &[u8]) -> DynamicImage {
+    let mut decoder = ImageReader::new(Cursor::new(bytes))
+        .with_guessed_format()
+        .expect("Could not open image")
+        .into_decoder()
+        .expect("Could not open image");
+    let orientation = decoder
+        .orientation()
+        .unwrap_or(image::metadata::Orientation::NoTransforms);
+    let icc = decoder.icc_profile().ok().flatten();
+    let exif = decoder.exif_metadata().ok().flatten();
+    let mut img = DynamicImage::from_decoder(decoder).expect("Could not open image");
+    img.apply_orientation(orientation);
+
+    // Only RGB profiles, the data color space signature is at byte 16 of the header
+    let profile = match icc {
+        Some(icc) if icc.get(16..20) == Some(b"RGB ") => qcms::Profile::new_from_slice(&icc, false),
+        _ if exif.is_some_and(exif_is_adobe_rgb) => adobe_rgb(),
+        _ => None,
+    };
+    match profile {
+        Some(profile) => to_srgb(img, &profile),
+        None => img,
+    }
+}
+
+// Decode anything that isn't an animation
+pub fn decode_still(bytes: &[u8], config: &Config) -> DynamicImage {
+    let img = if is_svg(bytes) {
+        rasterize_svg(bytes, config.svg_size)
+    } else if is_jxl(bytes) {
+        decode_external(bytes, "djxl", "jxl")
+    } else if let Ok(ImageFormat::Avif) = image::guess_format(bytes) {
+        decode_external(bytes, "avifdec", "avif")
+    } else {
+        decode_tagged(bytes)
+    };
+    tone_map(img, config.exposure)
+}
+
+pub fn is_supported(path: &Path) -> bool {
+    let extension = path
+        .extension()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .to_lowercase();
+    extension == "svg" || extension == "jxl" || ImageFormat::from_path(path).is_ok()
+}
@@ -0,0 +1,86 @@
+use image::codecs::gif::GifDecoder;
+use image::codecs::png::PngDecoder;
+use image::codecs::webp::WebPDecoder;
+use image::{AnimationDecoder, DynamicImage, ImageFormat};
+use std::io::Cursor;
+
+use super::config::Config;
+use super::formats;
+
+pub struct Frame {
+    pub image: DynamicImage,
+    // How long the frame is shown in milliseconds, still images get 1
+    pub duration: u64,
+}
+
+fn animation_frames(bytes: &[u8]) -> Option<Vec<Frame>> {
+    let frames = match image::guess_format(bytes).ok()? {
+        ImageFormat::Gif => GifDecoder::new(Cursor::new(bytes)).ok()?.into_frames(),
+        ImageFormat::Png => {
+            let decoder = PngDecoder::new(Cursor::new(bytes)).ok()?;
+            if !decoder.is_apng().ok()? {
+                return None;
+            }
+            decoder.apng().ok()?.into_frames()
+        }
+        ImageFormat::WebP => {
+            let decoder = WebPDecoder::new(Cursor::new(bytes)).ok()?;
+            if !decoder.has_animation() {
+                return None;
+            }
+            decoder.into_frames()
+        }
+        _ => return None,
+    };
+    let frames = frames
+        .collect_frames()
+        .expect("Could not decode animation frames");
+    Some(
+        frames
+            .into_iter()
+            .map(|frame| {
+                let (numer, denom) = frame.delay().numer_denom_ms();
+                // Browsers show frames without a delay for 100ms, so do the same
+                let duration = match numer / denom.max(1) {
+                    0 => 100,
+                    ms => u64::from(ms),
+                };
+                Frame {
+                    image: DynamicImage::ImageRgba8(frame.into_buffer()),
+                    duration,
+                }
+            })
+            .collect(),
+    )
+}
+
+// Pick `limit` evenly spaced frames, each one standing in for the frames up to the
+// next pick so the durations still add up to the whole animation
+fn sample(frames: Vec<Frame>, limit: usize) -> Vec<Frame> {
+    if limit == 0 || frames.len() <= limit {
+        return frames;
+    }
+    let total = frames.len();
+    let picks: Vec<usize> = (0..limit).map(|i| i * total / limit).collect();
+    let mut output = Vec::new();
+    for (index, frame) in frames.into_iter().enumerate() {
+        if picks.contains(&index) {
+            output.push(frame);
+        } else if let Some(last) = output.last_mut() {
+            last.duration += frame.duration;
+        }
+    }
+    output
+}
+
+// Every frame of animated GIF/APNG/WebP (or config.frames of them, 0 for all),
+// anything else is decoded as a single still frame
+pub fn decode(bytes: &[u8], config: &Config) -> Vec<Frame> {
+    match animation_frames(bytes) {
+        Some(frames) if !frames.is_empty() => sample(frames, config.frames),
+        _ => vec![Frame {
+            image: formats::decode_still(bytes, config),
+            duration: 1,
+        }],
+    }
+}
@@ -0,0 +1,92 @@
+use std::io;
+use std::os::unix::process::CommandExt;
+use std::process::{Child, Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+pub struct Hook {
+    pub command: String,
+    pub timeout: Duration,
+}
+
+// Hooks run through the shell with the scheme path in REEWAL_SCHEME and every
+// color as REEWAL_BASE00..REEWAL_BASE0F, hex without the leading #. Each one gets its
+// own process group, so whatever the shell starts can be killed along with it
+fn spawn(hook: &Hook, scheme: &str, palette: &[Option<(u8, u8, u8)>]) -> Result<Child, String> {
+    let mut command = Command::new("sh");
+    command
+        .arg("-c")
+        .arg(&hook.command)
+        .env("REEWAL_SCHEME", scheme)
+        .stdin(Stdio::null())
+        // stdout may be carrying the scheme
+        .stdout(io::stderr())
+        .process_group(0);
+    for color in palette.iter().enumerate() {
+        if let Some(c) = color.1 {
+            command.env(
+                format!("REEWAL_BASE{:02X}", color.0),
+                format!("{:02X}{:02X}{:02X}", c.0, c.1, c.2),
+            );
+        }
+    }
+    command
+        .spawn()
+        .map_err(|e| format!("could not start: {}", e))
+}
+
+fn wait(child: &mut Child, hook: &Hook, deadline: Instant) -> Result<(), String> {
+    loop {
+        match child.try_wait() {
+            Ok(Some(status)) if status.success() => return Ok(()),
+            Ok(Some(status)) => return Err(format!("{}", status)),
+            Ok(None) if Instant::now() >= deadline => {
+                // The group id is the shell's pid, children left behind would keep
+                // running and hold on to our stderr
+                unsafe {
+                    libc::kill(-(child.id() as libc::pid_t), libc::SIGKILL);
+                }
+                let _ = child.wait();
+                return Err(format!("timed out after {:?}", hook.timeout));
+            }
+            Ok(None) => thread::sleep(Duration::from_millis(10)),
+            Err(e) => return Err(e.to_string()),
+        }
+    }
+}
+
+fn report(hook: &Hook, result: &Result<(), String>) {
+    match result {
+        Ok(()) => info!("Hook finished: {}", hook.command),
+        Err(e) => eprintln!("Hook failed: {}: {}", hook.command, e),
+    }
+}
+
+// Returns the number of hooks that failed
+pub fn run_hooks(
+    hooks: &[Hook],
+    parallel: bool,
+    scheme: &str,
+    palette: &[Option<(u8, u8, u8)>],
+) -> usize {
+    let mut failed = 0;
+    if parallel {
+        let started: Vec<(Result<Child, String>, Instant)> = hooks
+            .iter()
+            .map(|hook| (spawn(hook, scheme, palette), Instant::now() + hook.timeout))
+            .collect();
+        for (hook, (child, deadline)) in hooks.iter().zip(started) {
+            let result = child.and_then(|mut child| wait(&mut child, hook, deadline));
+            report(hook, &result);
+            failed += result.is_err() as usize;
+        }
+    } else {
+        for hook in hooks {
+            let result = spawn(hook, scheme, palette)
+                .and_then(|mut child| wait(&mut child, hook, Instant::now() + hook.timeout));
+            report(hook, &result);
+            failed += result.is_err() as usize;
+        }
+    }
+    failed
+}
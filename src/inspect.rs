@@ -0,0 +1,190 @@
+use serde_json::{json, Value};
+
+use super::color;
+use super::config::{self, Config};
+use super::data::{self, hex, Palette, SlotReport};
+use super::hash;
+use super::quantize::ColorCount;
+use super::tune::{self, Score};
+
+// Distance of the slot's color from its template color, weighted like map_colors
+fn template_distance(rgb: &(u8, u8, u8), index: usize, config: &Config) -> u16 {
+    color::compare_colors(
+        rgb,
+        &config.template_colors[index],
+        config.hue_compare,
+        config.chroma_compare,
+        config.light_compare,
+    )
+}
+
+fn note(report: &SlotReport, index: usize) -> String {
+    if report.locked {
+        "locked".to_string()
+    } else if index == 13 {
+        "accent".to_string()
+    } else if report.generated && index < 8 {
+        "interpolated".to_string()
+    } else if report.generated {
+        format!("generated after {} tweaks", report.tweaks)
+    } else {
+        String::new()
+    }
+}
+
+fn print_table(
+    palette: &Palette,
+    reports: &[SlotReport],
+    reason: &str,
+    score: &Score,
+    config: &Config,
+) {
+    println!("Accent: {}", reason);
+    println!(
+        "Score: {:.1} (fidelity {:.2}, distinctness {:.2}, hue {:.2}, contrast {:.2})",
+        score.total, score.fidelity, score.distinctness, score.hue, score.contrast
+    );
+    println!();
+    println!(
+        "{:<6}  {:<8}  {:>10}  {:<7}  {:>8}  {:>3}  Notes",
+        "Slot", "Template", "Candidates", "Color", "Pixels", "ΔE"
+    );
+    for (index, report) in reports.iter().enumerate() {
+        let (color, distance) = match palette[index] {
+            Some(rgb) => (
+                hex(&rgb),
+                template_distance(&rgb, index, config).to_string(),
+            ),
+            None => ("-".to_string(), "-".to_string()),
+        };
+        let pixels = match (&report.chosen, report.generated) {
+            (Some(chosen), false) => chosen.count.to_string(),
+            _ => "-".to_string(),
+        };
+        println!(
+            "base{:02X}  {:<8}  {:>10}  {:<7}  {:>8}  {:>3}  {}",
+            index,
+            hex(&config.template_colors[index]),
+            report.candidates,
+            color,
+            pixels,
+            distance,
+            note(report, index)
+        );
+        for eviction in &report.evicted {
+            println!(
+                "        evicted {} ({} px), ΔE {} from base{:02X} {} is under the similarity of {}",
+                hex(&eviction.color.rgb),
+                eviction.color.count,
+                eviction.distance,
+                eviction.conflict,
+                hex(&eviction.conflict_rgb),
+                config.similarity
+            );
+        }
+    }
+}
+
+fn slot_json(
+    index: usize,
+    rgb: Option<(u8, u8, u8)>,
+    report: &SlotReport,
+    config: &Config,
+) -> Value {
+    let evicted: Vec<Value> = report
+        .evicted
+        .iter()
+        .map(|x| {
+            json!({
+                "color": hex(&x.color.rgb),
+                "pixels": x.color.count,
+                "conflict": format!("base{:02X}", x.conflict),
+                "conflict_color": hex(&x.conflict_rgb),
+                "distance": x.distance,
+            })
+        })
+        .collect();
+    json!({
+        "slot": format!("base{:02X}", index),
+        "template": hex(&config.template_colors[index]),
+        "candidates": report.candidates,
+        "color": rgb.map(|x| hex(&x)),
+        "pixels": report.chosen.as_ref().filter(|_| !report.generated).map(|x| x.count),
+        "distance": rgb.map(|x| template_distance(&x, index, config)),
+        "accent": index == 13,
+        "generated": report.generated,
+        "locked": report.locked,
+        "tweaks": report.tweaks,
+        "evicted": evicted,
+    })
+}
+
+fn print_json(
+    palette: &Palette,
+    reports: &[SlotReport],
+    reason: &str,
+    score: &Score,
+    config: &Config,
+) {
+    let slots: Vec<Value> = reports
+        .iter()
+        .enumerate()
+        .map(|(index, report)| slot_json(index, palette[index], report, config))
+        .collect();
+    let output = json!({
+        "accent": {
+            "color": palette[13].map(|x| hex(&x)),
+            "reason": reason,
+        },
+        "score": {
+            "total": score.total,
+            "fidelity": score.fidelity,
+            "distinctness": score.distinctness,
+            "hue": score.hue,
+            "contrast": score.contrast,
+        },
+        "similarity": config.similarity,
+        "likeness": config.likeness,
+        "slots": slots,
+    });
+    println!(
+        "{}",
+        serde_json::to_string_pretty(&output).expect("Could not format json")
+    );
+}
+
+// Runs the whole generation without the palette cache and explains every slot
+pub fn run(mut args: Vec<String>) {
+    let format = config::take_flag(&mut args, "--format").unwrap_or_else(|| "table".to_string());
+    if !matches!(format.as_str(), "table" | "json") {
+        panic!("--format: Expected table or json, got {}", format);
+    }
+    if args.len() < 2 {
+        panic!("Insufficient arguments provided. An image location required");
+    }
+    let image = args.pop().expect("No image provided");
+    let mut config = config::parse_config(args);
+    let bytes = super::read_image(image);
+    if config.auto_tune {
+        tune::auto_tune(&bytes, &mut config);
+    }
+
+    let image_hash = hash::hash_bytes(&bytes);
+    let mut frames = None;
+    let mut colors = super::cached_histogram(&bytes, &mut frames, &config, image_hash, "hist");
+    let centered = super::needs_center(&config)
+        .then(|| super::cached_histogram(&bytes, &mut frames, &config, image_hash, "center"));
+    let histogram: Vec<ColorCount> = colors.clone().into_sorted_vec();
+    let dominant = data::dominant(&colors, &config);
+    let (accent, reason) = data::get_accent(&mut colors, centered.as_ref(), &config);
+    let mut color_map = data::map_colors(colors, &config);
+    let (palette, mut reports) = data::explain_palette(&mut color_map, accent, dominant, &config);
+    let score = tune::score(&palette, &histogram, &config);
+    reports[13].chosen = histogram.into_iter().find(|x| Some(x.rgb) == palette[13]);
+
+    if format == "json" {
+        print_json(&palette, &reports, &reason, &score, &config);
+    } else {
+        print_table(&palette, &reports, &reason, &score, &config);
+    }
+}
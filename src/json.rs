@@ -0,0 +1,80 @@
+use serde_json::{json, Map, Value};
+use std::collections::BinaryHeap;
+
+use super::color;
+use super::data::hex;
+use super::quantize::ColorCount;
+use super::yaml::write_scheme;
+
+fn round(value: f64, decimals: i32) -> f64 {
+    let scale = 10f64.powi(decimals);
+    (value * scale).round() / scale
+}
+
+fn color_json(rgb: &(u8, u8, u8)) -> Value {
+    let hsl = color::rgb_to_hsl(rgb);
+    let oklch = color::rgb_to_oklch(rgb);
+    json!({
+        "hex": hex(rgb),
+        "rgb": [rgb.0, rgb.1, rgb.2],
+        "hsl": {
+            "h": round(hsl.0 * 360.0, 1),
+            "s": round(hsl.1 * 100.0, 1),
+            "l": round(hsl.2 * 100.0, 1),
+        },
+        "oklch": {
+            "l": round(oklch.0, 4),
+            "c": round(oklch.1, 4),
+            "h": round(oklch.2, 1),
+        },
+    })
+}
+
+// Same palette as the yaml scheme, but slots keep their place and missing ones are null
+pub fn format_scheme(
+    scheme: &[Option<(u8, u8, u8)>],
+    histogram: Option<&BinaryHeap<ColorCount>>,
+) -> String {
+    let mut palette = Map::new();
+    for (index, rgb) in scheme.iter().enumerate() {
+        palette.insert(
+            format!("base{:02X}", index),
+            rgb.as_ref().map_or(Value::Null, color_json),
+        );
+    }
+    let mut output = json!({
+        "system": "base16",
+        "name": "reewal-generated",
+        "author": "reewal",
+        "variant": "dark",
+        "accent": scheme[13].as_ref().map_or(Value::Null, color_json),
+        "palette": palette,
+    });
+    if let Some(histogram) = histogram {
+        let colors: Vec<Value> = histogram
+            .clone()
+            .into_sorted_vec()
+            .iter()
+            .rev()
+            .map(|x| {
+                json!({
+                    "hex": hex(&x.rgb),
+                    "rgb": [x.rgb.0, x.rgb.1, x.rgb.2],
+                    "count": x.count,
+                })
+            })
+            .collect();
+        output["histogram"] = Value::Array(colors);
+    }
+    let mut output = serde_json::to_string_pretty(&output).expect("Could not format json");
+    output.push('\n');
+    output
+}
+
+pub fn export_scheme(
+    path: &String,
+    scheme: &[Option<(u8, u8, u8)>],
+    histogram: Option<&BinaryHeap<ColorCount>>,
+) {
+    write_scheme(path, &format_scheme(scheme, histogram));
+}
@@ -0,0 +1,58 @@
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::time::Instant;
+
+// Quiet only leaves warnings and errors, verbose adds stage timings and debug
+// explains how every slot of the palette was filled
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    Quiet,
+    Info,
+    Verbose,
+    Debug,
+}
+
+static LEVEL: AtomicU8 = AtomicU8::new(Level::Info as u8);
+
+pub fn set_level(level: Level) {
+    LEVEL.store(level as u8, Ordering::Relaxed);
+}
+
+pub fn enabled(level: Level) -> bool {
+    LEVEL.load(Ordering::Relaxed) >= level as u8
+}
+
+macro_rules! info {
+    ($($arg:tt)*) => {
+        if $crate::log::enabled($crate::log::Level::Info) {
+            eprintln!($($arg)*);
+        }
+    };
+}
+
+macro_rules! verbose {
+    ($($arg:tt)*) => {
+        if $crate::log::enabled($crate::log::Level::Verbose) {
+            eprintln!($($arg)*);
+        }
+    };
+}
+
+macro_rules! debug {
+    ($($arg:tt)*) => {
+        if $crate::log::enabled($crate::log::Level::Debug) {
+            eprintln!("  {}", format!($($arg)*));
+        }
+    };
+}
+
+// Runs one stage of the generation, reporting how long it took at verbose level
+pub fn timed<T>(stage: &str, f: impl FnOnce() -> T) -> T {
+    let start = Instant::now();
+    let output = f();
+    verbose!(
+        "{:<9} {:>9.2} ms",
+        stage,
+        start.elapsed().as_secs_f64() * 1000.0
+    );
+    output
+}
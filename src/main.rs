@@ -1,12 +1,38 @@
+#[macro_use]
+mod log;
+
+mod batch;
+mod blend;
+mod cache;
+mod clusters;
 mod color;
 mod config;
+mod cvd;
 mod data;
+mod diff;
+mod formats;
+mod frames;
+mod hash;
+mod hooks;
+mod inspect;
+mod json;
+mod pipeline;
 mod quantize;
+mod recolor;
+mod saliency;
+mod temperature;
+mod tune;
+mod watch;
 mod yaml;
+use std::collections::BinaryHeap;
 use std::env;
+use std::fs;
+use std::io::{self, Read};
+use std::path::Path;
 
+// "-" is left alone, it stands for stdin or stdout
 fn handle_path(path: String) -> String {
-    if path.starts_with("/") || path.starts_with("\\") {
+    if path == "-" || path.starts_with("/") || path.starts_with("\\") {
         path
     } else {
         let current_dir = std::env::current_dir().unwrap().display().to_string();
@@ -14,37 +40,267 @@ fn handle_path(path: String) -> String {
     }
 }
 
-fn main() {
-    let mut args: Vec<String> = env::args().collect();
+// "-" reads the image from stdin, the format is sniffed from the bytes either way
+fn read_image(image: String) -> Vec<u8> {
+    let path = handle_path(image);
+    if path == "-" {
+        info!("Reading image from stdin");
+        let mut bytes = Vec::new();
+        io::stdin()
+            .read_to_end(&mut bytes)
+            .expect("Could not read image from stdin");
+        bytes
+    } else {
+        info!("Reading image: {}", path);
+        fs::read(path).expect("Could not open image")
+    }
+}
+
+// "hist" is what the palette is built from, "center" is additionally center weighted
+// for the center accent strategy
+fn histogram(
+    frames: &[frames::Frame],
+    config: &config::Config,
+    kind: &str,
+) -> BinaryHeap<quantize::ColorCount> {
+    match kind {
+        "center" => log::timed("quantize", || {
+            quantize::quantize(frames, config.depth, config.background, |x| {
+                let weight = saliency::pixel_weight(x, config);
+                let center = quantize::center_weight(x.width(), x.height());
+                move |x, y| weight(x, y) * center(x, y)
+            })
+        }),
+        _ => log::timed("quantize", || {
+            quantize::quantize(frames, config.depth, config.background, |x| {
+                saliency::pixel_weight(x, config)
+            })
+        }),
+    }
+}
+
+// Histograms are loaded from the cache when possible, the image is only decoded
+// the first time one has to be computed
+fn cached_histogram(
+    bytes: &[u8],
+    frames: &mut Option<Vec<frames::Frame>>,
+    config: &config::Config,
+    image_hash: u64,
+    kind: &str,
+) -> BinaryHeap<quantize::ColorCount> {
+    let quantize_hash = config.quantize_hash();
+    if let Some(colors) = cache::load_histogram(image_hash, quantize_hash, kind) {
+        verbose!("Using cached {} histogram", kind);
+        return colors;
+    }
+    let frames =
+        frames.get_or_insert_with(|| log::timed("decode", || frames::decode(bytes, config)));
+    let colors = histogram(frames, config, kind);
+    debug!("{} clusters in the {} histogram", colors.len(), kind);
+    cache::save_histogram(image_hash, quantize_hash, kind, &colors);
+    colors
+}
+
+fn needs_center(config: &config::Config) -> bool {
+    matches!(config.accent_strategy, data::AccentStrategy::Center)
+}
+
+fn palette_from_histograms(
+    mut colors: BinaryHeap<quantize::ColorCount>,
+    centered: Option<BinaryHeap<quantize::ColorCount>>,
+    config: &config::Config,
+) -> (data::Palette, String) {
+    let dominant = data::dominant(&colors, config);
+    let (accent, reason) = log::timed("accent", || {
+        data::get_accent(&mut colors, centered.as_ref(), config)
+    });
+    let mut color_map = log::timed("mapping", || data::map_colors(colors, config));
+    let palette = log::timed("palette", || {
+        data::create_palette(&mut color_map, accent, dominant, config)
+    });
+    (palette, reason)
+}
+
+// Adjustments of the finished palette, applied after the cache so trying them out
+// doesn't need a new palette
+fn post_process(palette: data::Palette, config: &config::Config) -> data::Palette {
+    let palette = pipeline::apply(&palette, &config.pipeline);
+    match config.temperature {
+        Some(kelvin) => temperature::adapt_palette(&palette, kelvin),
+        None => palette,
+    }
+}
+
+// Decoding and quantizing are skipped when the cache already has the results,
+// the accent explanation is only available when the palette wasn't cached
+fn create_scheme(bytes: &[u8], config: &config::Config) -> (data::Palette, Option<String>) {
+    let image_hash = hash::hash_bytes(bytes);
+    let config_hash = config.hash();
+    if let Some(palette) = cache::load_palette(image_hash, config_hash) {
+        verbose!("Using cached palette");
+        return (post_process(palette, config), None);
+    }
+
+    let mut frames = None;
+    let colors = cached_histogram(bytes, &mut frames, config, image_hash, "hist");
+    let centered = needs_center(config)
+        .then(|| cached_histogram(bytes, &mut frames, config, image_hash, "center"));
+    let (palette, reason) = palette_from_histograms(colors, centered, config);
+    cache::save_palette(image_hash, config_hash, &palette);
+    (post_process(palette, config), Some(reason))
+}
+
+// One palette for every sampled frame of an animation, for animated theme transitions
+fn frame_schemes(bytes: &[u8], config: &config::Config) -> Vec<data::Palette> {
+    frames::decode(bytes, config)
+        .iter()
+        .map(|frame| {
+            let frame = std::slice::from_ref(frame);
+            let colors = histogram(frame, config, "hist");
+            let centered = needs_center(config).then(|| histogram(frame, config, "center"));
+            post_process(palette_from_histograms(colors, centered, config).0, config)
+        })
+        .collect()
+}
+
+// Writes the scheme in config.format, the histogram is only included for the
+// image the bytes belong to
+fn export_scheme(
+    save: &String,
+    palette: &[Option<(u8, u8, u8)>],
+    bytes: Option<&[u8]>,
+    config: &config::Config,
+) {
+    if config.format == "json" {
+        let histogram = bytes.filter(|_| config.histogram).map(|bytes| {
+            cached_histogram(bytes, &mut None, config, hash::hash_bytes(bytes), "hist")
+        });
+        json::export_scheme(save, palette, histogram.as_ref());
+    } else {
+        yaml::export_scheme(save, palette);
+    }
+}
+
+fn generate(mut args: Vec<String>) {
     if args.len() < 2 {
         panic!("Insufficient arguments provided. An image and save file locations required");
     }
     let mut save = args.pop().expect("No save location provided");
     let image = args.pop().expect("No image provided");
 
-    let config = config::parse_config(args);
+    let mut config = config::parse_config(args);
 
-    let path = handle_path(image);
-    println!("Reading image: {}", path);
-    let img = image::open(path).expect("Could not open image");
-    println!("Opened");
-
-    println!("Parsing image data");
-    let mut colors = quantize::quantize(&img, config.depth);
-    println!("Generating palette");
-    let accent = data::get_accent(&mut colors, config.vibrancy);
-    let mut color_map = data::map_colors(colors, &config);
-    let base16 = data::create_palette(&mut color_map, accent, &config);
-
-    println!("Generated scheme: ");
+    let bytes = read_image(image);
+    if config.auto_tune {
+        log::timed("tune", || tune::auto_tune(&bytes, &mut config));
+    }
+    info!("Generating palette");
+    let (base16, reason) = create_scheme(&bytes, &config);
+    match reason {
+        Some(reason) => info!("Accent: {}", reason),
+        None => info!("Using cached palette"),
+    }
+
+    info!("Generated scheme: ");
     for color in base16.iter().enumerate() {
         if let Some(c) = color.1 {
-            println!("Color rgb #{} #{:02X}{:02X}{:02X}", color.0, c.0, c.1, c.2);
+            info!("Color rgb #{} #{:02X}{:02X}{:02X}", color.0, c.0, c.1, c.2);
         } else {
-            println!("No color number {}", color.0);
+            info!("No color number {}", color.0);
         }
     }
+    if config.cvd_check {
+        cvd::check(&base16, &config);
+    }
     save = handle_path(save);
-    println!("Saving scheme to {}", save);
-    yaml::export_scheme(&save, &base16);
+    info!("Saving scheme to {}", save);
+    log::timed("export", || {
+        export_scheme(&save, &base16, Some(&bytes), &config)
+    });
+
+    if config.per_frame {
+        if save == "-" {
+            panic!(
+                "--per-frame: Frame schemes can't be written to stdout, a save file is required"
+            );
+        }
+        let path = Path::new(&save);
+        let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+        let extension = path.extension().unwrap_or_default().to_string_lossy();
+        for frame in frame_schemes(&bytes, &config).iter().enumerate() {
+            let frame_save = path
+                .with_file_name(format!("{}-frame{:03}.{}", stem, frame.0, extension))
+                .display()
+                .to_string();
+            info!("Saving frame {} scheme to {}", frame.0, frame_save);
+            export_scheme(&frame_save, frame.1, None, &config);
+        }
+    }
+    if !config.variants.is_empty() {
+        if save == "-" {
+            panic!("--variants: Variants can't be written to stdout, a save file is required");
+        }
+        let path = Path::new(&save);
+        let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+        let extension = path.extension().unwrap_or_default().to_string_lossy();
+        for (name, kelvin) in config.variants.clone() {
+            config.temperature = Some(kelvin);
+            let (variant, _) = create_scheme(&bytes, &config);
+            let variant_save = path
+                .with_file_name(format!("{}-{}.{}", stem, name, extension))
+                .display()
+                .to_string();
+            info!("Saving {} variant ({}K) to {}", name, kelvin, variant_save);
+            export_scheme(&variant_save, &variant, Some(&bytes), &config);
+        }
+    }
+    hooks::run_hooks(&config.hooks, config.parallel_hooks, &save, &base16);
+}
+
+fn main() {
+    let mut args: Vec<String> = env::args().collect();
+    // Verbosity switches may come before the subcommand, they stay in the arguments
+    let mut index = 1;
+    while index < args.len() && config::is_verbosity(&args, index) {
+        index += 1;
+    }
+    match args.get(index).map(|x| x.as_str()) {
+        Some("generate") => {
+            args.remove(index);
+            generate(args);
+        }
+        Some("batch") => {
+            args.remove(index);
+            batch::run(args);
+        }
+        Some("blend") => {
+            args.remove(index);
+            blend::run_blend(args);
+        }
+        Some("clusters") => {
+            args.remove(index);
+            clusters::run(args);
+        }
+        Some("diff") => {
+            args.remove(index);
+            diff::run(args);
+        }
+        Some("inspect") => {
+            args.remove(index);
+            inspect::run(args);
+        }
+        Some("recolor") => {
+            args.remove(index);
+            recolor::run(args);
+        }
+        Some("transition") => {
+            args.remove(index);
+            blend::run_transition(args);
+        }
+        Some("watch") => {
+            args.remove(index);
+            watch::run(args);
+        }
+        _ => generate(args),
+    }
 }
@@ -0,0 +1,152 @@
+use std::hash::{Hash, Hasher};
+
+use super::color;
+use super::data::Palette;
+
+// Post-processing of the finished palette, configured under `pipeline` in the config
+// file and applied in order. Amounts are percentages, so saturate 10 scales chroma
+// by 1.1 and lighten 10 adds 0.1 to OKLCH lightness
+pub enum Operation {
+    // Negative amounts desaturate
+    Saturate(f64),
+    // Negative amounts darken
+    Lighten(f64),
+    HueRotate(f64),
+    // Spreads lightness of the slots evenly from min to max, in the order it was
+    ContrastStretch { min: f64, max: f64 },
+    // Moves hues this far toward the accent (base0D) hue
+    Harmonize(f64),
+    ClampChroma(f64),
+}
+
+impl Hash for Operation {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        match self {
+            Operation::Saturate(x) => (0, x.to_bits()).hash(state),
+            Operation::Lighten(x) => (1, x.to_bits()).hash(state),
+            Operation::HueRotate(x) => (2, x.to_bits()).hash(state),
+            Operation::ContrastStretch { min, max } => {
+                (3, min.to_bits(), max.to_bits()).hash(state)
+            }
+            Operation::Harmonize(x) => (4, x.to_bits()).hash(state),
+            Operation::ClampChroma(x) => (5, x.to_bits()).hash(state),
+        }
+    }
+}
+
+#[derive(Hash)]
+pub struct Stage {
+    pub operation: Operation,
+    pub slots: Vec<usize>,
+}
+
+// Slot group names, or a list of slot names like base08
+pub fn slot_group(name: &str) -> Option<Vec<usize>> {
+    match name {
+        "all" => Some((0..16).collect()),
+        "grayscale" => Some((0..8).collect()),
+        "accents" => Some((8..16).collect()),
+        _ => {
+            let index = usize::from_str_radix(name.strip_prefix("base")?, 16).ok()?;
+            (index < 16).then(|| vec![index])
+        }
+    }
+}
+
+fn rotate(hue: f64, degrees: f64) -> f64 {
+    (hue + degrees).rem_euclid(360.0)
+}
+
+// Signed shortest way around the hue circle from one hue to the other
+fn hue_difference(from: f64, to: f64) -> f64 {
+    let d = (to - from).rem_euclid(360.0);
+    if d > 180.0 {
+        d - 360.0
+    } else {
+        d
+    }
+}
+
+// Maps lightness linearly so the darkest slot ends up at min and the lightest at max,
+// equal slots stay equal and the spacing between them is kept
+fn contrast_stretch(palette: &mut Palette, slots: &[usize], min: f64, max: f64) {
+    let present: Vec<(usize, (f64, f64, f64))> = slots
+        .iter()
+        .filter_map(|x| palette[*x].map(|rgb| (*x, color::rgb_to_oklch(&rgb))))
+        .collect();
+    let lightness = present.iter().map(|x| x.1 .0);
+    let low = lightness.clone().fold(f64::INFINITY, f64::min);
+    let high = lightness.fold(f64::NEG_INFINITY, f64::max);
+    // Nothing to stretch when every slot has the same lightness
+    if present.is_empty() || high - low < 1e-9 {
+        return;
+    }
+    for (index, lch) in present {
+        let l = min + (lch.0 - low) / (high - low) * (max - min);
+        palette[index] = Some(color::oklch_to_rgb(&(l, lch.1, lch.2)));
+    }
+}
+
+pub fn apply(palette: &[Option<(u8, u8, u8)>], stages: &[Stage]) -> Palette {
+    let mut palette = palette.to_vec();
+    for stage in stages {
+        if let Operation::ContrastStretch { min, max } = stage.operation {
+            contrast_stretch(&mut palette, &stage.slots, min, max);
+            continue;
+        }
+        let accent_hue = palette[13].map(|x| color::rgb_to_oklch(&x).2);
+        for index in &stage.slots {
+            let Some(rgb) = palette[*index] else {
+                continue;
+            };
+            let (l, c, h) = color::rgb_to_oklch(&rgb);
+            let lch = match stage.operation {
+                Operation::Saturate(amount) => (l, c * (1.0 + amount / 100.0).max(0.0), h),
+                Operation::Lighten(amount) => ((l + amount / 100.0).clamp(0.0, 1.0), c, h),
+                Operation::HueRotate(degrees) => (l, c, rotate(h, degrees)),
+                Operation::Harmonize(amount) => match accent_hue {
+                    Some(accent) => (l, c, rotate(h, hue_difference(h, accent) * amount / 100.0)),
+                    None => (l, c, h),
+                },
+                Operation::ClampChroma(max) => (l, c.min(max), h),
+                Operation::ContrastStretch { .. } => unreachable!(),
+            };
+            palette[*index] = Some(color::oklch_to_rgb(&lch));
+        }
+    }
+    palette
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lightness(palette: &Palette, index: usize) -> f64 {
+        color::rgb_to_oklch(&palette[index].unwrap()).0
+    }
+
+    #[test]
+    fn contrast_keeps_equal_slots() {
+        let mut palette: Palette = vec![Some((235, 232, 225)); 4];
+        contrast_stretch(&mut palette, &[0, 1, 2, 3], 0.2, 0.95);
+        assert_eq!(palette, vec![Some((235, 232, 225)); 4]);
+    }
+
+    #[test]
+    fn contrast_keeps_order_and_spacing() {
+        let grays = [(40, 40, 40), (80, 80, 80), (90, 90, 90), (200, 200, 200)];
+        let mut palette: Palette = grays.iter().cloned().map(Some).collect();
+        let before: Vec<f64> = (0..4).map(|i| lightness(&palette, i)).collect();
+        contrast_stretch(&mut palette, &[0, 1, 2, 3], 0.2, 0.95);
+        let after: Vec<f64> = (0..4).map(|i| lightness(&palette, i)).collect();
+
+        assert!((after[0] - 0.2).abs() < 0.01);
+        assert!((after[3] - 0.95).abs() < 0.01);
+        assert!(after.windows(2).all(|x| x[0] < x[1]));
+        // Relative spacing is the same as before, up to rounding to 8 bits
+        let span = |l: &[f64], i: usize| (l[i] - l[0]) / (l[3] - l[0]);
+        for i in 1..3 {
+            assert!((span(&before, i) - span(&after, i)).abs() < 0.02);
+        }
+    }
+}
@@ -1,6 +1,8 @@
-use image::{GenericImageView, Pixel};
+use image::Pixel;
 use std::cmp::Ordering;
-use std::collections::{BinaryHeap, HashMap};
+use std::collections::{BTreeMap, BinaryHeap};
+
+use super::frames::Frame;
 
 #[derive(PartialEq, Eq, Clone)]
 pub struct ColorCount {
@@ -24,7 +26,7 @@ impl PartialOrd for ColorCount {
 }
 
 // Get a unique identifier for a color at a given depth
-fn get_pixel_hash(rgb: &[u8], depth: u8) -> String {
+pub fn get_pixel_hash(rgb: &[u8], depth: u8) -> String {
     let mut pixel_hash = String::new();
     for i in 0..depth {
         let r_bit = (rgb[0] >> (7 - i)) & 1;
@@ -40,49 +42,121 @@ fn get_pixel_hash(rgb: &[u8], depth: u8) -> String {
     pixel_hash
 }
 
-pub fn quantize(img: &image::DynamicImage, depth: u8) -> BinaryHeap<ColorCount> {
-    let mut tree: HashMap<String, (u64, u64, u64, u64)> = HashMap::new();
-
-    for (_x, _y, pixel) in img.pixels() {
-        let pixel_rgb = pixel.to_rgb();
-        let rgb = pixel_rgb.channels();
-        let pixel_hash = get_pixel_hash(rgb, depth);
-
-        if tree.contains_key(&pixel_hash) {
-            let v = tree.get(&pixel_hash).unwrap();
-            tree.insert(
-                pixel_hash,
-                (
-                    v.0 + 1,
-                    v.1 + u64::from(pixel_rgb[0]),
-                    v.2 + u64::from(pixel_rgb[1]),
-                    v.3 + u64::from(pixel_rgb[2]),
-                ),
-            );
-        } else {
-            tree.insert(
-                pixel_hash,
-                (
-                    1,
-                    pixel_rgb[0].into(),
-                    pixel_rgb[1].into(),
-                    pixel_rgb[2].into(),
-                ),
-            );
+fn composite(rgba: &[u16], background: &(u8, u8, u8)) -> [u16; 3] {
+    let alpha = u32::from(rgba[3]);
+    let blend = |c: u16, b: u8| {
+        ((u32::from(c) * alpha + u32::from(b) * 257 * (65535 - alpha) + 32767) / 65535) as u16
+    };
+    [
+        blend(rgba[0], background.0),
+        blend(rgba[1], background.1),
+        blend(rgba[2], background.2),
+    ]
+}
+
+// 16 bit color of a pixel as it's quantized and how much its alpha lets it count,
+// None when it doesn't count at all
+pub fn pixel_color(rgba: &[u16], background: Option<(u8, u8, u8)>) -> Option<([u16; 3], u128)> {
+    match background {
+        Some(bg) => Some((composite(rgba, &bg), 1)),
+        None if rgba[3] == 0 => None,
+        None => Some(([rgba[0], rgba[1], rgba[2]], u128::from(rgba[3]))),
+    }
+}
+
+// Bucket a 16 bit color falls in at a given depth
+pub fn bucket_hash(rgb: &[u16; 3], depth: u8) -> String {
+    get_pixel_hash(&rgb.map(|x| (x >> 8) as u8), depth)
+}
+
+// Pixels contribute `weight(x, y)` to their bucket instead of a flat count of one.
+// Transparent pixels are either composited onto the background or, without one,
+// weighted by their alpha so fully transparent areas don't count at all.
+// Frames of an animation are merged, weighted by how long each one is shown
+pub fn quantize<W, F>(
+    frames: &[Frame],
+    depth: u8,
+    background: Option<(u8, u8, u8)>,
+    weight: W,
+) -> BinaryHeap<ColorCount>
+where
+    W: Fn(&image::DynamicImage) -> F,
+    F: Fn(u32, u32) -> u64,
+{
+    clusters(frames, depth, background, weight)
+        .into_values()
+        .collect()
+}
+
+// quantize keyed by the bucket hash every cluster was averaged from, ordered so
+// the output never depends on hashing
+pub fn clusters<W, F>(
+    frames: &[Frame],
+    depth: u8,
+    background: Option<(u8, u8, u8)>,
+    weight: W,
+) -> BTreeMap<String, ColorCount>
+where
+    W: Fn(&image::DynamicImage) -> F,
+    F: Fn(u32, u32) -> u64,
+{
+    // Sums of long animations can get past u64
+    let mut tree: BTreeMap<String, (u128, u128, u128, u128)> = BTreeMap::new();
+
+    for frame in frames {
+        let pixel_weight = weight(&frame.image);
+        // Averaged at 16 bits per channel so high bit depth images aren't rounded first
+        let pixels = frame.image.to_rgba16();
+        for (x, y, pixel) in pixels.enumerate_pixels() {
+            let w = u128::from(pixel_weight(x, y) * frame.duration);
+            if w == 0 {
+                continue;
+            }
+            let Some((pixel_rgb, alpha)) = pixel_color(pixel.channels(), background) else {
+                continue;
+            };
+            let w = w * alpha;
+            let pixel_hash = bucket_hash(&pixel_rgb, depth);
+
+            let v = tree.entry(pixel_hash).or_insert((0, 0, 0, 0));
+            v.0 += w;
+            v.1 += w * u128::from(pixel_rgb[0]);
+            v.2 += w * u128::from(pixel_rgb[1]);
+            v.3 += w * u128::from(pixel_rgb[2]);
         }
     }
 
-    let mut output: BinaryHeap<ColorCount> = BinaryHeap::new();
-    for leaf in tree.into_values() {
-        let color = (
-            (leaf.1 / leaf.0) as u8,
-            (leaf.2 / leaf.0) as u8,
-            (leaf.3 / leaf.0) as u8,
+    // Counts go back to pixels of a frame averaged over time, rounding up so barely
+    // visible colors aren't lost
+    let alpha_scale: u128 = if background.is_none() { 65535 } else { 1 };
+    let total_duration: u128 = frames.iter().map(|x| u128::from(x.duration)).sum();
+    let scale = (alpha_scale * total_duration).max(1);
+
+    let mut output: BTreeMap<String, ColorCount> = BTreeMap::new();
+    for (pixel_hash, leaf) in tree {
+        let to_u8 = |sum: u128| ((sum / leaf.0 + 128) / 257) as u8;
+        let color = (to_u8(leaf.1), to_u8(leaf.2), to_u8(leaf.3));
+        output.insert(
+            pixel_hash,
+            ColorCount {
+                rgb: color,
+                count: leaf.0.div_ceil(scale) as usize,
+            },
         );
-        output.push(ColorCount {
-            rgb: color,
-            count: leaf.0 as usize,
-        });
     }
     output
 }
+
+// Normalized distance from the middle of the image, 0 in the center and 1 in the corners
+pub fn center_distance(x: u32, y: u32, width: u32, height: u32) -> f64 {
+    let cx = width as f64 / 2.0;
+    let cy = height as f64 / 2.0;
+    let dx = (x as f64 + 0.5 - cx) / cx;
+    let dy = (y as f64 + 0.5 - cy) / cy;
+    ((dx.powi(2) + dy.powi(2)) / 2.0).sqrt().min(1.0)
+}
+
+// Pixels in the middle of the image count 16 times, falling off to nothing in the corners
+pub fn center_weight(width: u32, height: u32) -> impl Fn(u32, u32) -> u64 {
+    move |x, y| (16.0 * (1.0 - center_distance(x, y, width, height)).powi(2)).round() as u64
+}
@@ -0,0 +1,192 @@
+use image::{DynamicImage, Rgba};
+use std::collections::HashMap;
+
+use super::color;
+use super::config;
+use super::frames;
+use super::log;
+use super::yaml;
+
+enum Metric {
+    Oklab,
+    Ciede2000,
+}
+
+enum Dither {
+    None,
+    FloydSteinberg,
+    Ordered,
+}
+
+const BAYER: [[f64; 4]; 4] = [
+    [0.0, 8.0, 2.0, 10.0],
+    [12.0, 4.0, 14.0, 6.0],
+    [3.0, 11.0, 1.0, 9.0],
+    [15.0, 7.0, 13.0, 5.0],
+];
+
+// How far ordered dithering pushes a channel either way
+const ORDERED_SPREAD: f64 = 64.0;
+
+struct Matcher {
+    palette: Vec<(u8, u8, u8)>,
+    oklab: Vec<(f64, f64, f64)>,
+    metric: Metric,
+    // Photos repeat a lot of colors, the perceptual metrics aren't cheap
+    cache: HashMap<(u8, u8, u8), (u8, u8, u8)>,
+}
+
+impl Matcher {
+    fn new(palette: Vec<(u8, u8, u8)>, metric: Metric) -> Matcher {
+        Matcher {
+            oklab: palette.iter().map(color::rgb_to_oklab).collect(),
+            palette,
+            metric,
+            cache: HashMap::new(),
+        }
+    }
+
+    fn nearest(&mut self, rgb: (u8, u8, u8)) -> (u8, u8, u8) {
+        if let Some(found) = self.cache.get(&rgb) {
+            return *found;
+        }
+        let index = match self.metric {
+            Metric::Oklab => {
+                let lab = color::rgb_to_oklab(&rgb);
+                let distance = |x: &(f64, f64, f64)| {
+                    (x.0 - lab.0).powi(2) + (x.1 - lab.1).powi(2) + (x.2 - lab.2).powi(2)
+                };
+                (0..self.oklab.len())
+                    .min_by(|a, b| distance(&self.oklab[*a]).total_cmp(&distance(&self.oklab[*b])))
+                    .unwrap()
+            }
+            Metric::Ciede2000 => (0..self.palette.len())
+                .min_by_key(|x| color::compare_colors(&rgb, &self.palette[*x], 1.0, 1.0, 1.0))
+                .unwrap(),
+        };
+        self.cache.insert(rgb, self.palette[index]);
+        self.palette[index]
+    }
+}
+
+fn to_rgb(value: [f64; 3]) -> (u8, u8, u8) {
+    let channel = |x: f64| x.round().clamp(0.0, 255.0) as u8;
+    (channel(value[0]), channel(value[1]), channel(value[2]))
+}
+
+// Every pixel mapped to its nearest palette color, alpha is kept as is
+fn recolor(
+    img: &DynamicImage,
+    matcher: &mut Matcher,
+    dither: &Dither,
+    strength: f64,
+) -> DynamicImage {
+    let mut pixels = img.to_rgba8();
+    let (width, height) = pixels.dimensions();
+    // Floyd-Steinberg pushes the error of every pixel onto its unvisited neighbours
+    let mut error = vec![[0.0f64; 3]; (width * height) as usize];
+
+    for y in 0..height {
+        for x in 0..width {
+            let pixel = pixels.get_pixel(x, y).0;
+            let original = [pixel[0] as f64, pixel[1] as f64, pixel[2] as f64];
+            let index = (y * width + x) as usize;
+            let wanted = match dither {
+                Dither::None => original,
+                // Clamped so colors the palette can't reach don't pile up error forever
+                Dither::FloydSteinberg => {
+                    [0, 1, 2].map(|c| (original[c] + error[index][c]).clamp(0.0, 255.0))
+                }
+                Dither::Ordered => {
+                    let offset = (BAYER[(y % 4) as usize][(x % 4) as usize] + 0.5) / 16.0 - 0.5;
+                    original.map(|c| c + offset * ORDERED_SPREAD)
+                }
+            };
+            let mapped = matcher.nearest(to_rgb(wanted));
+            let mapped = [mapped.0 as f64, mapped.1 as f64, mapped.2 as f64];
+
+            if let Dither::FloydSteinberg = dither {
+                let diff = [0, 1, 2].map(|c| wanted[c] - mapped[c]);
+                let mut spread = |dx: i64, dy: i64, share: f64| {
+                    let (nx, ny) = (x as i64 + dx, y as i64 + dy);
+                    if nx >= 0 && nx < width as i64 && ny < height as i64 {
+                        let target = &mut error[(ny as u32 * width + nx as u32) as usize];
+                        for c in 0..3 {
+                            target[c] += diff[c] * share;
+                        }
+                    }
+                };
+                spread(1, 0, 7.0 / 16.0);
+                spread(-1, 1, 3.0 / 16.0);
+                spread(0, 1, 5.0 / 16.0);
+                spread(1, 1, 1.0 / 16.0);
+            }
+
+            let blended =
+                to_rgb([0, 1, 2].map(|c| original[c] + (mapped[c] - original[c]) * strength));
+            pixels.put_pixel(x, y, Rgba([blended.0, blended.1, blended.2, pixel[3]]));
+        }
+    }
+    DynamicImage::ImageRgba8(pixels)
+}
+
+// Remaps an image to a base16 scheme, the one given with --scheme or otherwise the
+// palette generated from the image itself. Animations only get their first frame
+pub fn run(mut args: Vec<String>) {
+    let scheme = config::take_flag(&mut args, "--scheme");
+    let metric = match config::take_flag(&mut args, "--metric").as_deref() {
+        None | Some("oklab") => Metric::Oklab,
+        Some("ciede2000") => Metric::Ciede2000,
+        Some(other) => panic!("--metric: Expected oklab or ciede2000, got {}", other),
+    };
+    let dither = match config::take_flag(&mut args, "--dither").as_deref() {
+        None | Some("none") => Dither::None,
+        Some("floyd-steinberg") => Dither::FloydSteinberg,
+        Some("ordered") => Dither::Ordered,
+        Some(other) => panic!(
+            "--dither: Expected none, floyd-steinberg or ordered, got {}",
+            other
+        ),
+    };
+    let strength: u8 = config::take_flag(&mut args, "--strength")
+        .map(|x| x.parse().expect("--strength: Expected 0-100"))
+        .unwrap_or(100);
+    if strength > 100 {
+        panic!(
+            "--strength: Incorrect value. Expected 0-100, got {}",
+            strength
+        );
+    }
+    if args.len() < 3 {
+        panic!("Insufficient arguments provided. An image and output image locations required");
+    }
+    let save = super::handle_path(args.pop().expect("No output image provided"));
+    let image = args.pop().expect("No image provided");
+    let config = config::parse_config(args);
+    let bytes = super::read_image(image);
+
+    let palette: Vec<(u8, u8, u8)> = match scheme {
+        Some(path) => yaml::read_scheme(&super::handle_path(path))
+            .into_iter()
+            .flatten()
+            .collect(),
+        None => super::create_scheme(&bytes, &config)
+            .0
+            .into_iter()
+            .flatten()
+            .collect(),
+    };
+    let mut matcher = Matcher::new(palette, metric);
+    let frame = frames::decode(&bytes, &config).swap_remove(0);
+    let output = log::timed("recolor", || {
+        recolor(&frame.image, &mut matcher, &dither, strength as f64 / 100.0)
+    });
+    // Formats like jpeg can't store alpha, don't add any that wasn't there
+    let output = if frame.image.color().has_alpha() {
+        output
+    } else {
+        DynamicImage::ImageRgb8(output.into_rgb8())
+    };
+    output.save(&save).expect("Could not save recolored image");
+    info!("Saved recolored image to {}", save);
+}
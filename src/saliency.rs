@@ -0,0 +1,158 @@
+use image::GenericImageView;
+use std::hash::{Hash, Hasher};
+
+use super::color;
+use super::config::Config;
+use super::quantize::center_distance;
+
+pub enum Length {
+    Pixels(u32),
+    Percent(f64),
+}
+
+impl Length {
+    pub fn parse(value: &str) -> Option<Length> {
+        match value.strip_suffix('%') {
+            Some(percent) => percent.trim().parse().ok().map(Length::Percent),
+            None => value.trim().parse().ok().map(Length::Pixels),
+        }
+    }
+
+    fn resolve(&self, size: u32) -> u32 {
+        match self {
+            Length::Pixels(x) => *x,
+            Length::Percent(x) => (size as f64 * x / 100.0).round() as u32,
+        }
+    }
+}
+
+impl Hash for Length {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        match self {
+            Length::Pixels(x) => (0, *x as u64).hash(state),
+            Length::Percent(x) => (1, x.to_bits()).hash(state),
+        }
+    }
+}
+
+// Rectangle of the wallpaper that is covered by something, like a bar or a dock
+#[derive(Hash)]
+pub struct Mask {
+    pub x: Length,
+    pub y: Length,
+    pub width: Length,
+    pub height: Length,
+}
+
+impl Mask {
+    // x,y,width,height where every value is in pixels or a percentage of the image
+    pub fn parse(value: &str) -> Option<Mask> {
+        let parts: Vec<&str> = value.split(',').collect();
+        if parts.len() != 4 {
+            return None;
+        }
+        Some(Mask {
+            x: Length::parse(parts[0])?,
+            y: Length::parse(parts[1])?,
+            width: Length::parse(parts[2])?,
+            height: Length::parse(parts[3])?,
+        })
+    }
+
+    fn resolve(&self, width: u32, height: u32) -> (u32, u32, u32, u32) {
+        let x = self.x.resolve(width);
+        let y = self.y.resolve(height);
+        (
+            x,
+            y,
+            x.saturating_add(self.width.resolve(width)),
+            y.saturating_add(self.height.resolve(height)),
+        )
+    }
+}
+
+// Frequency tuned saliency, distance of every pixel of a small blurred copy from the
+// average color of the image, scaled to 0-1. Whole vivid subjects stand out instead
+// of just their edges
+struct SaliencyMap {
+    width: u32,
+    height: u32,
+    values: Vec<f64>,
+}
+
+impl SaliencyMap {
+    fn new(img: &image::DynamicImage) -> SaliencyMap {
+        let small = img.thumbnail(64, 64).to_rgb8();
+        let lab: Vec<(f64, f64, f64)> = small
+            .pixels()
+            .map(|x| color::rgb_to_lab(&(x[0], x[1], x[2])))
+            .collect();
+        let count = lab.len().max(1) as f64;
+        let mean = lab.iter().fold((0.0, 0.0, 0.0), |acc, x| {
+            (
+                acc.0 + x.0 / count,
+                acc.1 + x.1 / count,
+                acc.2 + x.2 / count,
+            )
+        });
+        let mut values: Vec<f64> = lab
+            .iter()
+            .map(|x| {
+                ((x.0 - mean.0).powi(2) + (x.1 - mean.1).powi(2) + (x.2 - mean.2).powi(2)).sqrt()
+            })
+            .collect();
+        let max = values.iter().cloned().fold(0.0, f64::max);
+        if max > 0.0 {
+            values.iter_mut().for_each(|x| *x /= max);
+        }
+        SaliencyMap {
+            width: small.width(),
+            height: small.height(),
+            values,
+        }
+    }
+
+    fn get(&self, x: u32, y: u32, width: u32, height: u32) -> f64 {
+        let sx = (x as u64 * self.width as u64 / width as u64) as u32;
+        let sy = (y as u64 * self.height as u64 / height as u64) as u32;
+        self.values[(sy.min(self.height - 1) * self.width + sx.min(self.width - 1)) as usize]
+    }
+}
+
+// Weight of every pixel for quantize::quantize and clusters. Masked pixels are ignored,
+// with no bias or saliency the rest count once, otherwise up to 16 times depending on
+// how central and how salient they are
+pub fn pixel_weight(img: &image::DynamicImage, config: &Config) -> impl Fn(u32, u32) -> u64 {
+    let (width, height) = img.dimensions();
+    let masks: Vec<(u32, u32, u32, u32)> = config
+        .masks
+        .iter()
+        .map(|x| x.resolve(width, height))
+        .collect();
+    let center_bias = config.center_bias as f64 / 100.0;
+    let saliency = config.saliency as f64 / 100.0;
+    let saliency_map = if config.saliency > 0 {
+        Some(SaliencyMap::new(img))
+    } else {
+        None
+    };
+
+    move |x, y| {
+        if masks
+            .iter()
+            .any(|m| x >= m.0 && x < m.2 && y >= m.1 && y < m.3)
+        {
+            return 0;
+        }
+        if center_bias == 0.0 && saliency == 0.0 {
+            return 1;
+        }
+        let distance = center_distance(x, y, width, height);
+        let center = 1.0 - center_bias + center_bias * (1.0 - distance).powi(2);
+        let salient = match &saliency_map {
+            Some(map) => 1.0 - saliency + saliency * map.get(x, y, width, height),
+            None => 1.0,
+        };
+        ((16.0 * center * salient).round() as u64).max(1)
+    }
+}
@@ -0,0 +1,120 @@
+use super::color;
+use super::data::Palette;
+
+type Matrix = [[f64; 3]; 3];
+
+const SRGB_TO_XYZ: Matrix = [
+    [0.4124564, 0.3575761, 0.1804375],
+    [0.2126729, 0.7151522, 0.0721750],
+    [0.0193339, 0.1191920, 0.9503041],
+];
+
+const XYZ_TO_SRGB: Matrix = [
+    [3.2404542, -1.5371385, -0.4985314],
+    [-0.9692660, 1.8760108, 0.0415560],
+    [0.0556434, -0.2040259, 1.0572252],
+];
+
+const BRADFORD: Matrix = [
+    [0.8951, 0.2664, -0.1614],
+    [-0.7502, 1.7135, 0.0367],
+    [0.0389, -0.0685, 1.0296],
+];
+
+const BRADFORD_INVERSE: Matrix = [
+    [0.9869929, -0.1470543, 0.1599627],
+    [0.4323053, 0.5183603, 0.0492912],
+    [-0.0085287, 0.0400428, 0.9684867],
+];
+
+// Color temperature of D65 on the daylight locus
+const D65: f64 = 6504.0;
+
+fn multiply(m: &Matrix, v: [f64; 3]) -> [f64; 3] {
+    m.map(|row| row[0] * v[0] + row[1] * v[1] + row[2] * v[2])
+}
+
+// Chromaticity of a color temperature, the CIE daylight locus from 4000K up and the
+// Planckian locus (Kim et al. cubic fit) below, where daylight isn't defined
+fn chromaticity(kelvin: f64) -> (f64, f64) {
+    let t = kelvin.clamp(1667.0, 25000.0);
+    if t >= 4000.0 {
+        let x = if t <= 7000.0 {
+            -4.6070e9 / t.powi(3) + 2.9678e6 / t.powi(2) + 0.09911e3 / t + 0.244063
+        } else {
+            -2.0064e9 / t.powi(3) + 1.9018e6 / t.powi(2) + 0.24748e3 / t + 0.237040
+        };
+        (x, -3.0 * x.powi(2) + 2.870 * x - 0.275)
+    } else {
+        let x = -0.2661239e9 / t.powi(3) - 0.2343589e6 / t.powi(2) + 0.8776956e3 / t + 0.179910;
+        let y = if t <= 2222.0 {
+            -1.1063814 * x.powi(3) - 1.34811020 * x.powi(2) + 2.18555832 * x - 0.20219683
+        } else {
+            -0.9549476 * x.powi(3) - 1.37418593 * x.powi(2) + 2.09137015 * x - 0.16748867
+        };
+        (x, y)
+    }
+}
+
+fn white_point(kelvin: f64) -> [f64; 3] {
+    let (x, y) = chromaticity(kelvin);
+    [x / y, 1.0, (1.0 - x - y) / y]
+}
+
+// Bradford chromatic adaptation from D65 to the color temperature, what the color
+// would look like lit by it. Lower is warmer
+pub fn adapt(rgb: &(u8, u8, u8), kelvin: f64) -> (u8, u8, u8) {
+    let source = multiply(&BRADFORD, white_point(D65));
+    let target = multiply(&BRADFORD, white_point(kelvin));
+
+    let linear = [
+        color::srgb_to_linear(rgb.0),
+        color::srgb_to_linear(rgb.1),
+        color::srgb_to_linear(rgb.2),
+    ];
+    let cone = multiply(&BRADFORD, multiply(&SRGB_TO_XYZ, linear));
+    let adapted = [0, 1, 2].map(|i| cone[i] * target[i] / source[i]);
+    let output = multiply(&XYZ_TO_SRGB, multiply(&BRADFORD_INVERSE, adapted));
+    (
+        color::linear_to_srgb(output[0]),
+        color::linear_to_srgb(output[1]),
+        color::linear_to_srgb(output[2]),
+    )
+}
+
+pub fn adapt_palette(palette: &[Option<(u8, u8, u8)>], kelvin: f64) -> Palette {
+    palette
+        .iter()
+        .map(|x| x.map(|x| adapt(&x, kelvin)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn d65_leaves_colors_alone() {
+        for rgb in [(255, 255, 255), (204, 36, 29), (40, 120, 230), (18, 18, 18)] {
+            let adapted = adapt(&rgb, D65);
+            let off = |a: u8, b: u8| (a as i16 - b as i16).abs();
+            assert!(off(adapted.0, rgb.0) <= 1 && off(adapted.1, rgb.1) <= 1);
+            assert!(off(adapted.2, rgb.2) <= 1, "{:?} became {:?}", rgb, adapted);
+        }
+    }
+
+    #[test]
+    fn lower_temperature_is_warmer() {
+        let warm = adapt(&(200, 200, 200), 2700.0);
+        let cool = adapt(&(200, 200, 200), 10000.0);
+        assert!(warm.0 > warm.2);
+        assert!(cool.2 > cool.0);
+    }
+
+    #[test]
+    fn missing_slots_stay_missing() {
+        let palette = adapt_palette(&[None, Some((128, 128, 128))], 4000.0);
+        assert_eq!(palette[0], None);
+        assert!(palette[1].is_some());
+    }
+}
@@ -0,0 +1,159 @@
+use std::panic::{self, AssertUnwindSafe};
+
+use super::color;
+use super::config::Config;
+use super::data::{self, Palette};
+use super::hash;
+use super::quantize::ColorCount;
+
+pub struct Score {
+    // How close the image's clusters are to some palette color
+    pub fidelity: f64,
+    // Smallest distance between two of base08-base0F
+    pub distinctness: f64,
+    // How well the accent slots keep the hue of their template color
+    pub hue: f64,
+    // Contrast of the accent slots against the background
+    pub contrast: f64,
+    pub total: f64,
+}
+
+fn luminance(rgb: &(u8, u8, u8)) -> f64 {
+    0.2126 * color::srgb_to_linear(rgb.0)
+        + 0.7152 * color::srgb_to_linear(rgb.1)
+        + 0.0722 * color::srgb_to_linear(rgb.2)
+}
+
+fn contrast_ratio(c1: &(u8, u8, u8), c2: &(u8, u8, u8)) -> f64 {
+    let (l1, l2) = (luminance(c1), luminance(c2));
+    (l1.max(l2) + 0.05) / (l1.min(l2) + 0.05)
+}
+
+// Every part is 0-1, the total is their weighted sum scaled to 0-100
+pub fn score(palette: &[Option<(u8, u8, u8)>], histogram: &[ColorCount], config: &Config) -> Score {
+    let colors: Vec<(u8, u8, u8)> = palette.iter().flatten().cloned().collect();
+    let total_count: usize = histogram.iter().map(|x| x.count).sum();
+    let weighted: f64 = histogram
+        .iter()
+        .map(|x| {
+            let distance = colors
+                .iter()
+                .map(|c| color::compare_colors(&x.rgb, c, 1.0, 1.0, 1.0))
+                .min()
+                .unwrap_or(u16::MAX);
+            distance as f64 * x.count as f64
+        })
+        .sum();
+    let fidelity = 1.0 - (weighted / total_count.max(1) as f64).min(50.0) / 50.0;
+
+    let accents: Vec<(usize, (u8, u8, u8))> =
+        (8..16).filter_map(|i| palette[i].map(|x| (i, x))).collect();
+    let mut closest = u16::MAX;
+    for (a, c1) in accents.iter().enumerate() {
+        for (_, c2) in &accents[a + 1..] {
+            closest = closest.min(color::compare_colors(&c1.1, c2, 1.0, 1.0, 1.0));
+        }
+    }
+    let distinctness = (closest as f64).min(40.0) / 40.0;
+
+    let hue_offsets: Vec<f64> = accents
+        .iter()
+        .filter(|x| x.0 != 13)
+        .map(|(i, rgb)| {
+            let d = (color::get_hue(rgb) - color::get_hue(&config.template_colors[*i])).abs();
+            d.min(360.0 - d) / 180.0
+        })
+        .collect();
+    let hue = 1.0 - hue_offsets.iter().sum::<f64>() / hue_offsets.len().max(1) as f64;
+
+    let background = palette[0].unwrap_or(config.template_colors[0]);
+    let contrast = accents
+        .iter()
+        .map(|x| ((contrast_ratio(&x.1, &background) - 1.0) / 3.5).clamp(0.0, 1.0))
+        .sum::<f64>()
+        / accents.len().max(1) as f64;
+
+    Score {
+        fidelity,
+        distinctness,
+        hue,
+        contrast,
+        total: 100.0 * (0.3 * fidelity + 0.3 * distinctness + 0.2 * hue + 0.2 * contrast),
+    }
+}
+
+// Runs f with panics caught and not printed, the previous hook is back before
+// anything else can panic
+fn quietly<T>(f: impl FnOnce() -> T) -> std::thread::Result<T> {
+    let hook = panic::take_hook();
+    panic::set_hook(Box::new(|_| {}));
+    let result = panic::catch_unwind(AssertUnwindSafe(f));
+    panic::set_hook(hook);
+    result
+}
+
+const DEPTHS: [u8; 3] = [2, 3, 4];
+const LIKENESS: [u16; 5] = [10, 15, 20, 30, 40];
+const SIMILARITY: [u16; 5] = [10, 15, 20, 25, 30];
+const VIBRANCY: [u8; 5] = [5, 10, 15, 25, 35];
+
+// Grid search over depth, likeness, similarity and vibrancy, leaving the best scoring
+// combination in the config. Combinations that fail, like a vibrancy nothing in the
+// image reaches, are skipped
+pub fn auto_tune(bytes: &[u8], config: &mut Config) {
+    let image_hash = hash::hash_bytes(bytes);
+    let mut frames = None;
+    let mut best: Option<(f64, u8, u16, u16, u8)> = None;
+    for depth in DEPTHS {
+        config.depth = depth;
+        let colors = super::cached_histogram(bytes, &mut frames, config, image_hash, "hist");
+        let histogram: Vec<ColorCount> = colors.clone().into_sorted_vec();
+        let centered = super::needs_center(config)
+            .then(|| super::cached_histogram(bytes, &mut frames, config, image_hash, "center"));
+        for likeness in LIKENESS {
+            for similarity in SIMILARITY {
+                for vibrancy in VIBRANCY {
+                    config.likeness = likeness;
+                    config.similarity = similarity;
+                    config.vibrancy = vibrancy;
+                    let tried: &Config = config;
+                    // Same as palette_from_histograms, without timing every stage. Failed
+                    // combinations would spam the output
+                    let result = quietly(|| -> Palette {
+                        let mut colors = colors.clone();
+                        let dominant = data::dominant(&colors, tried);
+                        let (accent, _) = data::get_accent(&mut colors, centered.as_ref(), tried);
+                        let mut color_map = data::map_colors(colors, tried);
+                        data::create_palette(&mut color_map, accent, dominant, tried)
+                    });
+                    let Ok(palette) = result else {
+                        continue;
+                    };
+                    let score = score(&palette, &histogram, config).total;
+                    debug!(
+                        "-d {} -l {} -s {} -v {}: score {:.1}",
+                        depth, likeness, similarity, vibrancy, score
+                    );
+                    if best.is_none_or(|x| score > x.0) {
+                        best = Some((score, depth, likeness, similarity, vibrancy));
+                    }
+                }
+            }
+        }
+    }
+
+    let (score, depth, likeness, similarity, vibrancy) =
+        best.expect("--auto-tune: No combination of parameters worked for this image");
+    config.depth = depth;
+    config.likeness = likeness;
+    config.similarity = similarity;
+    config.vibrancy = vibrancy;
+    info!(
+        "Auto-tuned parameters, score {:.1}, for a config file:",
+        score
+    );
+    info!("depth: {}", depth);
+    info!("likeness: {}", likeness);
+    info!("similarity: {}", similarity);
+    info!("vibrancy: {}", vibrancy);
+}
@@ -0,0 +1,224 @@
+use inotify::{Inotify, WatchDescriptor, WatchMask};
+use std::collections::hash_map::{Entry, HashMap};
+use std::env;
+use std::ffi::OsString;
+use std::fs;
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::panic;
+use std::path::PathBuf;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use super::batch::panic_message;
+use super::config::{self, Config};
+use super::data::Palette;
+use super::hooks;
+
+enum Event {
+    Changed,
+    // Carries the connection waiting for the regenerated palette
+    Regenerate(Sender<String>),
+    // The wallpaper can't be watched anymore, there won't be any other change
+    Failed(String),
+}
+
+fn default_socket() -> String {
+    match env::var("XDG_RUNTIME_DIR") {
+        Ok(dir) if !dir.is_empty() => format!("{}/reewal.sock", dir),
+        _ => "/tmp/reewal.sock".to_string(),
+    }
+}
+
+fn format_palette(palette: &[Option<(u8, u8, u8)>]) -> String {
+    let mut output = String::new();
+    for color in palette.iter().enumerate() {
+        match color.1 {
+            Some(c) => {
+                output +=
+                    format!("base{:02X} #{:02X}{:02X}{:02X}\n", color.0, c.0, c.1, c.2).as_str()
+            }
+            None => output += format!("base{:02X} -\n", color.0).as_str(),
+        }
+    }
+    output
+}
+
+// Watches the directories holding the wallpaper and whatever it links to, so both
+// replacing the file and retargeting a symlink are noticed. Directories the wallpaper
+// no longer links into stop being watched
+fn watch_wallpaper(path: PathBuf, events: &Sender<Event>) -> Result<(), String> {
+    let mut inotify =
+        Inotify::init().map_err(|e| format!("Could not initialize inotify: {}", e))?;
+    let mask = WatchMask::CLOSE_WRITE | WatchMask::MOVED_TO | WatchMask::CREATE;
+    let mut watched: HashMap<PathBuf, WatchDescriptor> = HashMap::new();
+    let mut buffer = [0; 4096];
+    loop {
+        let mut names: Vec<OsString> = Vec::new();
+        let mut dirs: Vec<PathBuf> = Vec::new();
+        let target = fs::canonicalize(&path).unwrap_or(path.clone());
+        for file in [&path, &target] {
+            if let (Some(dir), Some(name)) = (file.parent(), file.file_name()) {
+                dirs.push(dir.to_path_buf());
+                names.push(name.to_os_string());
+            }
+        }
+        // A directory that is gone has lost its watch already, removing it can fail
+        watched.retain(|dir, wd| {
+            dirs.contains(dir) || {
+                let _ = inotify.watches().remove(wd.clone());
+                false
+            }
+        });
+        for dir in dirs {
+            if let Entry::Vacant(entry) = watched.entry(dir) {
+                let dir = entry.key();
+                let wd = inotify
+                    .watches()
+                    .add(dir, mask)
+                    .map_err(|e| format!("Could not watch directory {}: {}", dir.display(), e))?;
+                entry.insert(wd);
+            }
+        }
+
+        let read = inotify
+            .read_events_blocking(&mut buffer)
+            .map_err(|e| format!("Could not read inotify events: {}", e))?;
+        let changed = read
+            .filter_map(|event| event.name)
+            .any(|name| names.iter().any(|x| x == name));
+        if changed && events.send(Event::Changed).is_err() {
+            return Ok(());
+        }
+    }
+}
+
+fn handle_client(stream: UnixStream, palette: &Mutex<Palette>, events: &Sender<Event>) {
+    let mut writer = match stream.try_clone() {
+        Ok(writer) => writer,
+        Err(_) => return,
+    };
+    for line in BufReader::new(stream).lines() {
+        let Ok(line) = line else {
+            return;
+        };
+        let response = match line.trim() {
+            "palette" => format_palette(&palette.lock().unwrap()),
+            "regenerate" => {
+                let (reply, response) = mpsc::channel();
+                if events.send(Event::Regenerate(reply)).is_err() {
+                    return;
+                }
+                response
+                    .recv()
+                    .unwrap_or_else(|_| "error: Watcher stopped\n".to_string())
+            }
+            command => format!("error: Unknown command {}\n", command),
+        };
+        if writer.write_all(response.as_bytes()).is_err() {
+            return;
+        }
+    }
+}
+
+// Every connection gets its own thread, so an idle client doesn't hold up the others
+fn listen(listener: UnixListener, palette: Arc<Mutex<Palette>>, events: Sender<Event>) {
+    for stream in listener.incoming().flatten() {
+        let palette = Arc::clone(&palette);
+        let events = events.clone();
+        thread::spawn(move || handle_client(stream, &palette, &events));
+    }
+}
+
+fn regenerate(wallpaper: &str, save: &str, config: &Config) -> Option<Palette> {
+    info!("Regenerating scheme from {}", wallpaper);
+    let result = panic::catch_unwind(|| {
+        let bytes = fs::read(wallpaper).expect("Could not open image");
+        let (base16, _) = super::create_scheme(&bytes, config);
+        super::export_scheme(&save.to_string(), &base16, Some(&bytes), config);
+        base16
+    });
+    match result {
+        Ok(base16) => {
+            info!("Saved scheme to {}", save);
+            hooks::run_hooks(&config.hooks, config.parallel_hooks, save, &base16);
+            Some(base16)
+        }
+        Err(payload) => {
+            eprintln!(
+                "Keeping previous scheme, generation failed: {}",
+                panic_message(payload)
+            );
+            None
+        }
+    }
+}
+
+// Collect everything that arrives until the wallpaper has been quiet for the debounce time
+fn wait_for_changes(events: &Receiver<Event>, debounce: Duration) -> Option<Vec<Sender<String>>> {
+    let mut replies = Vec::new();
+    let mut event = events.recv().ok()?;
+    loop {
+        match event {
+            Event::Regenerate(reply) => replies.push(reply),
+            Event::Failed(message) => panic!("Stopped watching the wallpaper. {}", message),
+            Event::Changed => {}
+        }
+        event = match events.recv_timeout(debounce) {
+            Ok(event) => event,
+            Err(RecvTimeoutError::Timeout) => return Some(replies),
+            Err(RecvTimeoutError::Disconnected) => return None,
+        };
+    }
+}
+
+pub fn run(mut args: Vec<String>) {
+    let socket = config::take_flag(&mut args, "--socket")
+        .map(super::handle_path)
+        .unwrap_or_else(default_socket);
+    let debounce: u64 = config::take_flag(&mut args, "--debounce")
+        .map(|x| {
+            x.parse()
+                .expect("--debounce: Expected a time in milliseconds")
+        })
+        .unwrap_or(250);
+    if args.len() < 3 {
+        panic!("Insufficient arguments provided. A wallpaper and save file locations required");
+    }
+    let save = super::handle_path(args.pop().expect("No save location provided"));
+    let wallpaper = super::handle_path(args.pop().expect("No wallpaper provided"));
+    let config = config::parse_config(args);
+
+    let palette = Arc::new(Mutex::new(
+        regenerate(&wallpaper, &save, &config).unwrap_or_default(),
+    ));
+    let (sender, events) = mpsc::channel();
+
+    // A socket left behind by a previous run would make bind fail
+    let _ = fs::remove_file(&socket);
+    let listener = UnixListener::bind(&socket).expect("Could not create socket");
+    info!("Listening on {}", socket);
+    let socket_palette = Arc::clone(&palette);
+    let socket_sender = sender.clone();
+    thread::spawn(move || listen(listener, socket_palette, socket_sender));
+
+    let watched = PathBuf::from(&wallpaper);
+    thread::spawn(move || {
+        if let Err(message) = watch_wallpaper(watched, &sender) {
+            let _ = sender.send(Event::Failed(message));
+        }
+    });
+    info!("Watching {}", wallpaper);
+
+    while let Some(replies) = wait_for_changes(&events, Duration::from_millis(debounce)) {
+        if let Some(base16) = regenerate(&wallpaper, &save, &config) {
+            *palette.lock().unwrap() = base16;
+        }
+        let response = format_palette(&palette.lock().unwrap());
+        for reply in replies {
+            let _ = reply.send(response.clone());
+        }
+    }
+}
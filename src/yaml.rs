@@ -1,10 +1,10 @@
 use saphyr::Yaml;
 use std::{
     fs::File,
-    io::{Read, Write},
+    io::{self, Read, Write},
 };
 
-fn hex_to_rgb(hex: &str) -> (u8, u8, u8) {
+pub fn hex_to_rgb(hex: &str) -> (u8, u8, u8) {
     let offset = match hex.len() {
         6 => 0,
         7 => 1,
@@ -16,29 +16,49 @@ fn hex_to_rgb(hex: &str) -> (u8, u8, u8) {
     (r, g, b)
 }
 
-pub fn get_scheme(path: &String) -> Vec<(u8, u8, u8)> {
-    println!("Opening scheme file: {}", path);
+// Slots are read by name, missing or null ones are None
+pub fn read_scheme(path: &String) -> Vec<Option<(u8, u8, u8)>> {
+    info!("Opening scheme file: {}", path);
     let mut content = String::new();
     let mut file = File::open(path).expect("Could not open scheme file");
     file.read_to_string(&mut content)
         .expect("Could not read scheme file");
-    println!("Opened");
     let docs = Yaml::load_from_str(&content).expect("Could not load scheme file as yaml");
-    let out: Vec<(u8, u8, u8)> = docs[0]["palette"]
-        .as_hash()
-        .unwrap()
-        .iter()
-        .map(|(_k, v)| hex_to_rgb(v.as_str().unwrap()))
-        .collect();
-
-    if out.len() != 16 {
-        panic!("Not a base16 paltte, expected 16 colors, got {}", out.len());
+    let palette = &docs[0]["palette"];
+    if palette.as_hash().is_none() {
+        panic!("Not a base16 palette, expected a palette section");
     }
-    out
+    (0..16)
+        .map(|index| {
+            // Plenty of schemes write base0a instead of base0A
+            let upper = format!("base{:02X}", index);
+            let lower = format!("base{:02x}", index);
+            palette[upper.as_str()]
+                .as_str()
+                .or(palette[lower.as_str()].as_str())
+                .map(hex_to_rgb)
+        })
+        .collect()
 }
 
-pub fn export_scheme(path: &String, scheme: &Vec<Option<(u8, u8, u8)>>) {
-    let mut file = File::create(path).expect("Could not save file");
+// Templates need every slot
+pub fn get_scheme(path: &String) -> Vec<(u8, u8, u8)> {
+    read_scheme(path)
+        .into_iter()
+        .enumerate()
+        .map(|(index, rgb)| {
+            rgb.unwrap_or_else(|| {
+                panic!(
+                    "Not a complete base16 palette, base{:02X} is missing",
+                    index
+                )
+            })
+        })
+        .collect()
+}
+
+// Every slot is written under its own index, missing ones as null like in the json scheme
+pub fn format_scheme(scheme: &[Option<(u8, u8, u8)>]) -> String {
     let mut output: String = r#"system: "base16"
 name: "reewal-generated"
 author: "reewal"
@@ -46,12 +66,33 @@ variant: "dark"
 palette:
 "#
     .to_string();
-    for color in scheme.iter().flatten().enumerate() {
-        output += format!(
-            "  base{:02X}: \"#{:02X}{:02X}{:02X}\"\n",
-            color.0, color.1 .0, color.1 .1, color.1 .2
-        )
+    for (index, color) in scheme.iter().enumerate() {
+        output += match color {
+            Some(rgb) => format!(
+                "  base{:02X}: \"#{:02X}{:02X}{:02X}\"\n",
+                index, rgb.0, rgb.1, rgb.2
+            ),
+            None => format!("  base{:02X}: null\n", index),
+        }
         .as_str();
     }
+    output
+}
+
+// "-" writes the scheme to stdout
+pub fn write_scheme(path: &String, output: &str) {
+    if path == "-" {
+        let mut stdout = io::stdout().lock();
+        stdout
+            .write_all(output.as_bytes())
+            .and_then(|_| stdout.flush())
+            .expect("Could not write scheme to stdout");
+        return;
+    }
+    let mut file = File::create(path).expect("Could not save file");
     let _ = file.write(output.as_bytes());
 }
+
+pub fn export_scheme(path: &String, scheme: &[Option<(u8, u8, u8)>]) {
+    write_scheme(path, &format_scheme(scheme));
+}
@@ -0,0 +1,294 @@
+use image::{Rgb, RgbImage};
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+// Generated schemes of a corpus of sample images, compared with the ones in
+// tests/golden. Run with REEWAL_UPDATE_GOLDEN=1 to write them again after a change
+// that is meant to alter the output
+
+const WIDTH: u32 = 96;
+const HEIGHT: u32 = 64;
+
+// Small deterministic noise, so images have more than a handful of clusters
+fn noise(x: u32, y: u32, seed: u32) -> i32 {
+    let mut n = x
+        .wrapping_mul(374761393)
+        .wrapping_add(y.wrapping_mul(668265263))
+        .wrapping_add(seed.wrapping_mul(2246822519));
+    n = (n ^ (n >> 13)).wrapping_mul(1274126177);
+    ((n ^ (n >> 16)) % 17) as i32 - 8
+}
+
+fn channel(value: i32) -> u8 {
+    value.clamp(0, 255) as u8
+}
+
+fn lerp(a: u8, b: u8, t: f64) -> i32 {
+    (a as f64 + (b as f64 - a as f64) * t).round() as i32
+}
+
+// Night sky at the top to orange at the horizon, with a dark ground
+fn sunset(x: u32, y: u32) -> Rgb<u8> {
+    let t = y as f64 / HEIGHT as f64;
+    let n = noise(x, y, 1);
+    if t > 0.8 {
+        return Rgb([channel(24 + n), channel(20 + n), channel(30 + n)]);
+    }
+    let (top, bottom) = ((30, 34, 90), (240, 120, 50));
+    let t = t / 0.8;
+    Rgb([
+        channel(lerp(top.0, bottom.0, t) + n),
+        channel(lerp(top.1, bottom.1, t) + n),
+        channel(lerp(top.2, bottom.2, t) + n),
+    ])
+}
+
+// Mostly greens and browns with some sky
+fn forest(x: u32, y: u32) -> Rgb<u8> {
+    let n = noise(x, y, 2);
+    let band = (x / 8 + y / 16) % 4;
+    if y < HEIGHT / 5 {
+        return Rgb([channel(150 + n), channel(190 + n), channel(225 + n)]);
+    }
+    match band {
+        0 => Rgb([channel(40 + n), channel(90 + n), channel(35 + n)]),
+        1 => Rgb([channel(70 + n), channel(130 + n), channel(50 + n)]),
+        2 => Rgb([channel(90 + n), channel(60 + n), channel(30 + n)]),
+        _ => Rgb([channel(20 + n), channel(45 + n), channel(25 + n)]),
+    }
+}
+
+// Eight saturated vertical bands over a light background
+fn stripes(x: u32, y: u32) -> Rgb<u8> {
+    const BANDS: [(i32, i32, i32); 8] = [
+        (220, 50, 60),
+        (245, 140, 40),
+        (240, 210, 50),
+        (80, 180, 60),
+        (40, 190, 170),
+        (40, 120, 230),
+        (150, 80, 210),
+        (170, 110, 50),
+    ];
+    let n = noise(x, y, 3);
+    if y < HEIGHT / 4 {
+        return Rgb([channel(235 + n), channel(232 + n), channel(225 + n)]);
+    }
+    let (r, g, b) = BANDS[(x * 8 / WIDTH) as usize];
+    Rgb([channel(r + n), channel(g + n), channel(b + n)])
+}
+
+// Checkerboard of pastels, colorful but light, on dark gray
+fn pastel(x: u32, y: u32) -> Rgb<u8> {
+    let n = noise(x, y, 4);
+    let (r, g, b) = match ((x / 12) + (y / 12)) % 5 {
+        0 => (250, 190, 200),
+        1 => (190, 230, 200),
+        2 => (190, 210, 250),
+        3 => (250, 230, 180),
+        _ => (45, 45, 50),
+    };
+    Rgb([channel(r + n), channel(g + n), channel(b + n)])
+}
+
+// Grays with a single colorful spot, where most slots have to be generated
+fn muted(x: u32, y: u32) -> Rgb<u8> {
+    let n = noise(x, y, 5);
+    let (dx, dy) = (x as i32 - 48, y as i32 - 32);
+    if dx * dx + dy * dy < 100 {
+        return Rgb([channel(200 + n), channel(60 + n), channel(140 + n)]);
+    }
+    let v = 30 + (x as i32 * 160 / WIDTH as i32);
+    Rgb([channel(v + n), channel(v + n), channel(v + 4 + n)])
+}
+
+// Mostly warm dark gray, with a blue and a smaller orange block below
+fn backdrop(x: u32, y: u32) -> Rgb<u8> {
+    let n = noise(x, y, 6);
+    let (r, g, b) = if y < HEIGHT * 3 / 5 {
+        (46, 42, 38)
+    } else if x < WIDTH * 2 / 3 {
+        (50, 110, 210)
+    } else {
+        (230, 140, 40)
+    };
+    Rgb([channel(r + n), channel(g + n), channel(b + n)])
+}
+
+// Only a blue and an orange, nothing the grayscale slots could take
+fn duotone(x: u32, y: u32) -> Rgb<u8> {
+    let n = noise(x, y, 7);
+    let (r, g, b) = if x < WIDTH * 2 / 3 {
+        (50, 110, 210)
+    } else {
+        (230, 140, 40)
+    };
+    Rgb([channel(r + n), channel(g + n), channel(b + n)])
+}
+
+fn work_dir(name: &str) -> PathBuf {
+    let dir = env::temp_dir().join(format!("reewal-golden-{}", name));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).expect("Could not create test directory");
+    dir
+}
+
+fn sample(dir: &Path, name: &str, pixel: fn(u32, u32) -> Rgb<u8>) -> PathBuf {
+    let path = dir.join(format!("{}.png", name));
+    RgbImage::from_fn(WIDTH, HEIGHT, pixel)
+        .save(&path)
+        .expect("Could not save sample image");
+    path
+}
+
+// Scheme printed to stdout, with its own cache directory
+fn generate(image: &Path, args: &[&str], cache: &Path) -> String {
+    let output = Command::new(env!("CARGO_BIN_EXE_reewal"))
+        .arg("-q")
+        .args(args)
+        .arg(image)
+        .arg("-")
+        .env("XDG_CACHE_HOME", cache)
+        .output()
+        .expect("Could not run reewal");
+    assert!(
+        output.status.success(),
+        "reewal failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    String::from_utf8(output.stdout).expect("Scheme is not utf-8")
+}
+
+// Two runs with fresh caches and one from the cache all have to match the golden file
+fn check(name: &str, pixel: fn(u32, u32) -> Rgb<u8>, args: &[&str]) {
+    let dir = work_dir(name);
+    let image = sample(&dir, name, pixel);
+    let first = generate(&image, args, &dir.join("cache-1"));
+    let second = generate(&image, args, &dir.join("cache-2"));
+    let cached = generate(&image, args, &dir.join("cache-1"));
+    assert_eq!(first, second, "{}: output differs between runs", name);
+    assert_eq!(first, cached, "{}: cached output differs", name);
+
+    let golden = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/golden")
+        .join(format!("{}.yaml", name));
+    if env::var_os("REEWAL_UPDATE_GOLDEN").is_some() {
+        fs::write(&golden, &first).expect("Could not write golden file");
+    } else {
+        let expected = fs::read_to_string(&golden)
+            .unwrap_or_else(|_| panic!("{}: Missing golden file {}", name, golden.display()));
+        assert_eq!(
+            first, expected,
+            "{}: output differs from the golden file",
+            name
+        );
+    }
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn golden_sunset() {
+    check("sunset", sunset, &[]);
+}
+
+#[test]
+fn golden_forest() {
+    check("forest", forest, &[]);
+}
+
+#[test]
+fn golden_stripes() {
+    check("stripes", stripes, &[]);
+}
+
+#[test]
+fn golden_pastel() {
+    check("pastel", pastel, &[]);
+}
+
+#[test]
+fn golden_muted() {
+    check("muted", muted, &[]);
+}
+
+#[test]
+fn golden_stripes_deep() {
+    check("stripes-deep", stripes, &["-d", "4"]);
+}
+
+#[test]
+fn golden_forest_complementary() {
+    check(
+        "forest-complementary",
+        forest,
+        &["--accent", "complementary"],
+    );
+}
+
+// The most frequent cluster is the gray backdrop, its hue shouldn't decide the accent
+#[test]
+fn golden_backdrop_complementary() {
+    check(
+        "backdrop-complementary",
+        backdrop,
+        &["--accent", "complementary"],
+    );
+}
+
+#[test]
+fn golden_sunset_gray_ramp() {
+    check("sunset-gray-ramp", sunset, &["--gray-ramp"]);
+}
+
+// Without grayscale candidates the ramp takes the tint of the wallpaper, not of the
+// locked accent
+#[test]
+fn golden_duotone_gray_ramp_locked() {
+    check(
+        "duotone-gray-ramp-locked",
+        duotone,
+        &["--gray-ramp", "--lock", "base0D=#20c020"],
+    );
+}
+
+// A locked middle gray is one of the ramp's steps, the others are spaced around it
+#[test]
+fn golden_sunset_gray_ramp_locked() {
+    check(
+        "sunset-gray-ramp-locked",
+        sunset,
+        &["--gray-ramp", "--lock", "base03=#808080"],
+    );
+}
+
+#[test]
+fn golden_stripes_locked() {
+    check("stripes-locked", stripes, &["--lock", "base08=#cc241d"]);
+}
+
+// A locked color stays in its own slot whatever the format, even when slots before
+// it are missing
+#[test]
+fn locked_slot_keeps_its_index() {
+    let dir = work_dir("locked-index");
+    let image = sample(&dir, "stripes", stripes);
+    let args = ["--lock", "base0B=#98971a"];
+    let yaml = generate(&image, &args, &dir.join("cache"));
+    assert!(
+        yaml.contains("  base0B: \"#98971A\"\n"),
+        "locked color not in base0B:\n{}",
+        yaml
+    );
+    assert_eq!(yaml.matches("  base").count(), 16);
+
+    let json = generate(
+        &image,
+        &["--format", "json", args[0], args[1]],
+        &dir.join("cache"),
+    );
+    let json: serde_json::Value = serde_json::from_str(&json).expect("Scheme is not json");
+    assert_eq!(json["palette"]["base0B"]["hex"], "#98971A");
+    let _ = fs::remove_dir_all(&dir);
+}